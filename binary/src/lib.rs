@@ -0,0 +1,31 @@
+use alloy_primitives::B256;
+use sha2::{Digest, Sha256};
+
+pub use tree::BinaryTree;
+pub use verkle::{stem::Stem, TrieKey};
+
+pub mod node;
+pub mod tree;
+
+/// Values are stored as raw 32 bytes.
+pub type TreeValue = B256;
+
+/// The depth of the stem nodes is at most the number of stem bits.
+pub const MAX_DEPTH: usize = 31 * 8;
+
+type Db = dyn db::Db<B256, Vec<u8>>;
+type WriteBatch = db::WriteBatch<B256, Vec<u8>>;
+
+/// The hash used for merkleization. Hash of 64 zero bytes is zero, so that empty subtrees don't
+/// have to be hashed.
+pub fn hash(data: &[u8]) -> B256 {
+    if data.len() == 64 && data.iter().all(|byte| *byte == 0) {
+        return B256::ZERO;
+    }
+    B256::from_slice(&Sha256::digest(data))
+}
+
+/// Returns the bit of the stem at the given depth, starting from the most significant bit.
+pub fn stem_bit(stem: &Stem, depth: usize) -> usize {
+    ((stem[depth / 8] >> (7 - depth % 8)) & 1) as usize
+}
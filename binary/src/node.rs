@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, mem};
+
+use alloy_primitives::B256;
+use anyhow::{bail, ensure, Result};
+
+use crate::{hash, stem_bit, Db, Stem, TreeValue, TrieKey, WriteBatch, MAX_DEPTH};
+
+const INTERNAL_NODE_TAG: u8 = 0;
+const STEM_NODE_TAG: u8 = 1;
+const STEM_LENGTH: usize = 31;
+
+pub enum Node {
+    Empty,
+    Internal(Box<InternalNode>),
+    Stem(Box<StemNode>),
+    /// The node that is stored in the Db, but not loaded yet.
+    Hash(B256),
+}
+
+/// The node with two children, selected by the stem bit at the depth of the node.
+pub struct InternalNode {
+    left: Node,
+    right: Node,
+    hash: Option<B256>,
+}
+
+/// The node with up to 256 values that share the same stem.
+pub struct StemNode {
+    stem: Stem,
+    values: BTreeMap<u8, TreeValue>,
+    hash: Option<B256>,
+}
+
+impl Node {
+    pub fn new_hash(hash: B256) -> Self {
+        if hash.is_zero() {
+            Self::Empty
+        } else {
+            Self::Hash(hash)
+        }
+    }
+
+    /// Loads the node from the Db, if it isn't loaded yet.
+    pub fn resolve(&mut self, db: &Db) -> Result<()> {
+        if let Self::Hash(hash) = self {
+            let Some(encoded) = db.read(hash)? else {
+                bail!("Node missing from Db: {hash:?}")
+            };
+            *self = Self::decode(&encoded, *hash)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&mut self, depth: usize, key: TrieKey, db: &Db) -> Result<Option<TreeValue>> {
+        self.resolve(db)?;
+        match self {
+            Self::Empty => Ok(None),
+            Self::Internal(internal_node) => internal_node
+                .child_mut(stem_bit(&key.stem(), depth))
+                .get(depth + 1, key, db),
+            Self::Stem(stem_node) if stem_node.stem == key.stem() => {
+                Ok(stem_node.values.get(&key.last()).copied())
+            }
+            Self::Stem(_) => Ok(None),
+            Self::Hash(_) => unreachable!("Node should be resolved"),
+        }
+    }
+
+    pub fn insert(&mut self, depth: usize, key: TrieKey, value: TreeValue, db: &Db) -> Result<()> {
+        self.resolve(db)?;
+        match self {
+            Self::Empty => {
+                let mut stem_node = StemNode::new(key.stem());
+                stem_node.set(key.last(), value);
+                *self = Self::Stem(stem_node.into());
+            }
+            Self::Internal(internal_node) => {
+                internal_node.hash = None;
+                internal_node
+                    .child_mut(stem_bit(&key.stem(), depth))
+                    .insert(depth + 1, key, value, db)?;
+            }
+            Self::Stem(stem_node) if stem_node.stem == key.stem() => {
+                stem_node.set(key.last(), value);
+            }
+            Self::Stem(_) => {
+                let Self::Stem(existing) = mem::replace(self, Self::Empty) else {
+                    unreachable!()
+                };
+                let mut stem_node = StemNode::new(key.stem());
+                stem_node.set(key.last(), value);
+                *self = Self::split(depth, existing, stem_node)?;
+            }
+            Self::Hash(_) => unreachable!("Node should be resolved"),
+        }
+        Ok(())
+    }
+
+    /// Creates the internal nodes down to the first depth where the stems differ.
+    fn split(depth: usize, existing: Box<StemNode>, new: StemNode) -> Result<Self> {
+        ensure!(depth < MAX_DEPTH, "Stems should be different");
+        let existing_bit = stem_bit(&existing.stem, depth);
+        let mut internal_node = InternalNode::new();
+        if existing_bit == stem_bit(&new.stem, depth) {
+            *internal_node.child_mut(existing_bit) = Self::split(depth + 1, existing, new)?;
+        } else {
+            *internal_node.child_mut(existing_bit) = Self::Stem(existing);
+            *internal_node.child_mut(1 - existing_bit) = Self::Stem(new.into());
+        }
+        Ok(Self::Internal(internal_node.into()))
+    }
+
+    /// Removes the value. Internal nodes with a single stem node below them are replaced by that
+    /// stem node, so the tree has the same shape as if the value was never inserted.
+    pub fn remove(&mut self, depth: usize, key: TrieKey, db: &Db) -> Result<()> {
+        self.resolve(db)?;
+        match self {
+            Self::Empty => {}
+            Self::Internal(internal_node) => {
+                internal_node.hash = None;
+                internal_node
+                    .child_mut(stem_bit(&key.stem(), depth))
+                    .remove(depth + 1, key, db)?;
+                if let Some(node) = internal_node.collapse(db)? {
+                    *self = node;
+                }
+            }
+            Self::Stem(stem_node) if stem_node.stem == key.stem() => {
+                stem_node.remove(key.last());
+                if stem_node.values.is_empty() {
+                    *self = Self::Empty;
+                }
+            }
+            Self::Stem(_) => {}
+            Self::Hash(_) => unreachable!("Node should be resolved"),
+        }
+        Ok(())
+    }
+
+    /// Returns the hash of the node, and writes all modified nodes into the batch.
+    pub fn write_and_hash(&mut self, batch: &mut WriteBatch) -> B256 {
+        match self {
+            Self::Empty => B256::ZERO,
+            Self::Internal(internal_node) => internal_node.write_and_hash(batch),
+            Self::Stem(stem_node) => stem_node.write_and_hash(batch),
+            Self::Hash(hash) => *hash,
+        }
+    }
+
+    fn decode(encoded: &[u8], hash: B256) -> Result<Self> {
+        match encoded.split_first() {
+            Some((&INTERNAL_NODE_TAG, children)) if children.len() == 64 => {
+                Ok(Self::Internal(Box::new(InternalNode {
+                    left: Self::new_hash(B256::from_slice(&children[..32])),
+                    right: Self::new_hash(B256::from_slice(&children[32..])),
+                    hash: Some(hash),
+                })))
+            }
+            Some((&STEM_NODE_TAG, stem_and_values)) if stem_and_values.len() >= STEM_LENGTH => {
+                let (stem, values) = stem_and_values.split_at(STEM_LENGTH);
+                let values = values.chunks_exact(33);
+                ensure!(
+                    values.remainder().is_empty(),
+                    "Invalid encoding of node {hash:?}"
+                );
+                Ok(Self::Stem(Box::new(StemNode {
+                    stem: TrieKey::new(B256::right_padding_from(stem)).stem(),
+                    values: values
+                        .map(|index_value| (index_value[0], B256::from_slice(&index_value[1..])))
+                        .collect(),
+                    hash: Some(hash),
+                })))
+            }
+            _ => bail!("Invalid encoding of node {hash:?}"),
+        }
+    }
+}
+
+impl InternalNode {
+    fn new() -> Self {
+        Self {
+            left: Node::Empty,
+            right: Node::Empty,
+            hash: None,
+        }
+    }
+
+    fn child_mut(&mut self, bit: usize) -> &mut Node {
+        if bit == 0 {
+            &mut self.left
+        } else {
+            &mut self.right
+        }
+    }
+
+    /// Returns the node that should replace this node, if it has less than two children.
+    fn collapse(&mut self, db: &Db) -> Result<Option<Node>> {
+        let child = match (&mut self.left, &mut self.right) {
+            (Node::Empty, Node::Empty) => return Ok(Some(Node::Empty)),
+            (Node::Empty, child) | (child, Node::Empty) => child,
+            _ => return Ok(None),
+        };
+        child.resolve(db)?;
+        Ok(match child {
+            // Stem node can be moved up, internal node is still needed to separate its children
+            Node::Stem(_) => Some(mem::replace(child, Node::Empty)),
+            _ => None,
+        })
+    }
+
+    fn write_and_hash(&mut self, batch: &mut WriteBatch) -> B256 {
+        if let Some(hash) = self.hash {
+            return hash;
+        }
+        let mut encoded = Vec::with_capacity(65);
+        encoded.push(INTERNAL_NODE_TAG);
+        encoded.extend_from_slice(self.left.write_and_hash(batch).as_slice());
+        encoded.extend_from_slice(self.right.write_and_hash(batch).as_slice());
+
+        let hash = hash(&encoded[1..]);
+        batch.write(hash, encoded);
+        self.hash = Some(hash);
+        hash
+    }
+}
+
+impl StemNode {
+    fn new(stem: Stem) -> Self {
+        Self {
+            stem,
+            values: BTreeMap::new(),
+            hash: None,
+        }
+    }
+
+    fn set(&mut self, index: u8, value: TreeValue) {
+        self.values.insert(index, value);
+        self.hash = None;
+    }
+
+    fn remove(&mut self, index: u8) {
+        if self.values.remove(&index).is_some() {
+            self.hash = None;
+        }
+    }
+
+    fn write_and_hash(&mut self, batch: &mut WriteBatch) -> B256 {
+        if let Some(hash) = self.hash {
+            return hash;
+        }
+        // Merkleization of all 256 values, missing values have zero hash
+        let mut level = vec![B256::ZERO; 256];
+        for (index, value) in &self.values {
+            level[*index as usize] = hash(value.as_slice());
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| hash(&[pair[0].as_slice(), pair[1].as_slice()].concat()))
+                .collect();
+        }
+        let mut stem_and_root = Vec::with_capacity(64);
+        stem_and_root.extend_from_slice(self.stem.as_slice());
+        stem_and_root.push(0);
+        stem_and_root.extend_from_slice(level[0].as_slice());
+        let hash = hash(&stem_and_root);
+
+        let mut encoded = Vec::with_capacity(1 + STEM_LENGTH + 33 * self.values.len());
+        encoded.push(STEM_NODE_TAG);
+        encoded.extend_from_slice(self.stem.as_slice());
+        for (index, value) in &self.values {
+            encoded.push(*index);
+            encoded.extend_from_slice(value.as_slice());
+        }
+        batch.write(hash, encoded);
+        self.hash = Some(hash);
+        hash
+    }
+}
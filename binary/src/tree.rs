@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use alloy_primitives::{Address, B256, U256};
+use anyhow::Result;
+use verkle::{
+    storage::{AccountStorageLayout, HeaderLayout, Sha256KeyHasher},
+    TrieValue,
+};
+
+use crate::{node::Node, Db, TreeValue, TrieKey, WriteBatch};
+
+/// The binary tree from EIP-7864, with SHA-256 merkleization.
+pub struct BinaryTree {
+    root: Node,
+    db: Box<Db>,
+}
+
+impl BinaryTree {
+    pub fn new(db: Box<Db>) -> Self {
+        Self {
+            root: Node::Empty,
+            db,
+        }
+    }
+
+    pub fn new_with_root(root: B256, db: Box<Db>) -> Self {
+        Self {
+            root: Node::new_hash(root),
+            db,
+        }
+    }
+
+    pub fn get(&mut self, key: TrieKey) -> Result<Option<TreeValue>> {
+        self.root.get(0, key, self.db.as_ref())
+    }
+
+    pub fn insert(&mut self, key: TrieKey, value: TreeValue) -> Result<()> {
+        self.root.insert(0, key, value, self.db.as_ref())
+    }
+
+    pub fn remove(&mut self, key: TrieKey) -> Result<()> {
+        self.root.remove(0, key, self.db.as_ref())
+    }
+
+    /// Returns the root hash, and writes all modified nodes to the Db.
+    pub fn root(&mut self) -> Result<B256> {
+        let mut batch = WriteBatch::new();
+        let root = self.root.write_and_hash(&mut batch);
+        self.db.write_batch(batch)?;
+        Ok(root)
+    }
+
+    /// Returns the storage layout of the account, with the EIP-7864 key derivation and the basic
+    /// data header.
+    pub fn account_storage(address: Address) -> AccountStorageLayout {
+        AccountStorageLayout::new_with_key_hasher(
+            address,
+            HeaderLayout::BasicData,
+            Arc::new(Sha256KeyHasher),
+        )
+    }
+
+    pub fn create_eoa(&mut self, address: Address, balance: U256, nonce: u64) -> Result<()> {
+        let storage = Self::account_storage(address);
+        self.insert_values(storage.account_header(balance, nonce, None)?)
+    }
+
+    pub fn create_sc(
+        &mut self,
+        address: Address,
+        balance: U256,
+        nonce: u64,
+        code: Vec<u8>,
+    ) -> Result<()> {
+        let storage = Self::account_storage(address);
+        let mut key_values = storage.account_header(balance, nonce, Some(&code))?;
+        key_values.extend(storage.chunkify_code(&code));
+        self.insert_values(key_values)
+    }
+
+    pub fn set_storage(&mut self, address: Address, slot: U256, value: B256) -> Result<()> {
+        self.insert(Self::account_storage(address).storage_slot_key(slot), value)
+    }
+
+    /// Inserts the values created by [AccountStorageLayout], which stores the raw bytes as little
+    /// endian [TrieValue].
+    fn insert_values(&mut self, key_values: Vec<(TrieKey, TrieValue)>) -> Result<()> {
+        for (key, value) in key_values {
+            self.insert(key, B256::from(value.to_le_bytes::<32>()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use claims::{assert_none, assert_some_eq};
+    use db::memory_db::MemoryDb;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rstest::rstest;
+
+    use super::*;
+
+    fn init() -> BinaryTree {
+        BinaryTree::new(Box::new(MemoryDb::new()))
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let mut tree = init();
+        assert_eq!(tree.root()?, B256::ZERO);
+        assert_none!(tree.get(TrieKey::new(B256::ZERO))?);
+        Ok(())
+    }
+
+    #[test]
+    fn insert_and_get() -> Result<()> {
+        let mut tree = init();
+        let key0 = TrieKey::new(B256::ZERO);
+        let key1 = TrieKey::new(B256::with_last_byte(1));
+        let key_max = TrieKey::new(B256::repeat_byte(0xff));
+
+        tree.insert(key0, B256::repeat_byte(1))?;
+        tree.insert(key1, B256::repeat_byte(2))?;
+        tree.insert(key_max, B256::repeat_byte(3))?;
+        assert_some_eq!(tree.get(key0)?, B256::repeat_byte(1));
+        assert_some_eq!(tree.get(key1)?, B256::repeat_byte(2));
+        assert_some_eq!(tree.get(key_max)?, B256::repeat_byte(3));
+        assert_none!(tree.get(TrieKey::new(B256::with_last_byte(2)))?);
+        assert_none!(tree.get(TrieKey::new(B256::left_padding_from(&[1, 0])))?);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(12345, 10)]
+    #[case(12345, 1000)]
+    fn remove_random(#[case] seed: u64, #[case] count: usize) -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut key_values = BTreeMap::new();
+        while key_values.len() < count {
+            let key = TrieKey::new(B256::random_with(&mut rng));
+            let key = match rng.gen_range(0..3) {
+                // Keys with the same stem
+                0 => TrieKey::from_stem_and_last_byte(&key.stem(), rng.gen()),
+                // Stems that differ only in the last byte, so the tree is deep
+                1 => TrieKey::new(B256::left_padding_from(&[rng.gen(), rng.gen()])),
+                _ => key,
+            };
+            key_values.insert(key, B256::random_with(&mut rng));
+        }
+        let (kept, removed): (Vec<_>, Vec<_>) = key_values
+            .into_iter()
+            .enumerate()
+            .partition(|(i, _)| i % 2 == 0);
+
+        let mut expected_tree = init();
+        for (_, (key, value)) in &kept {
+            expected_tree.insert(*key, *value)?;
+        }
+
+        let mut tree = init();
+        for (_, (key, value)) in kept.iter().chain(&removed) {
+            tree.insert(*key, *value)?;
+        }
+        tree.root()?;
+        for (_, (key, _)) in &removed {
+            tree.remove(*key)?;
+        }
+        for (_, (key, _)) in &removed {
+            assert_none!(tree.get(*key)?);
+        }
+        for (_, (key, value)) in &kept {
+            assert_some_eq!(tree.get(*key)?, *value);
+        }
+        assert_eq!(tree.root()?, expected_tree.root()?);
+        Ok(())
+    }
+
+    #[test]
+    fn reopen() -> Result<()> {
+        let mut tree = init();
+        let mut rng = StdRng::seed_from_u64(12345);
+        let key_values = (0..100)
+            .map(|_| {
+                (
+                    TrieKey::new(B256::random_with(&mut rng)),
+                    B256::random_with(&mut rng),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (key, value) in &key_values {
+            tree.insert(*key, *value)?;
+        }
+        let root = tree.root()?;
+
+        let BinaryTree { db, .. } = tree;
+        let mut tree = BinaryTree::new_with_root(root, db);
+        for (key, value) in &key_values {
+            assert_some_eq!(tree.get(*key)?, *value);
+        }
+        // Nodes loaded from the Db are modified and written again
+        tree.remove(key_values[0].0)?;
+        tree.insert(key_values[0].0, key_values[0].1)?;
+        assert_eq!(tree.root()?, root);
+        Ok(())
+    }
+}
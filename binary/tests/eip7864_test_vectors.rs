@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod eip7864_test_vectors {
+    // Expected roots are computed with the reference implementation from EIP-7864
+
+    use std::str::FromStr;
+
+    use alloy_primitives::{b256, Address, B256, U256};
+    use anyhow::Result;
+    use binary::{BinaryTree, TrieKey};
+    use db::memory_db::MemoryDb;
+
+    fn init() -> BinaryTree {
+        BinaryTree::new(Box::new(MemoryDb::new()))
+    }
+
+    fn root(key_values: &[(B256, B256)]) -> Result<B256> {
+        let mut tree = init();
+        for (key, value) in key_values {
+            tree.insert(TrieKey::new(*key), *value)?;
+        }
+        tree.root()
+    }
+
+    fn key(prefix: &[u8], last_byte: u8) -> B256 {
+        let mut key = B256::right_padding_from(prefix);
+        key[31] = last_byte;
+        key
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        assert_eq!(root(&[])?, B256::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn single_entry() -> Result<()> {
+        assert_eq!(
+            root(&[(B256::ZERO, B256::repeat_byte(1))])?,
+            b256!("aab1060e04cb4f5dc6f697ae93156a95714debbf77d54238766adc5709282b6f")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn two_entries_diff_first_bit() -> Result<()> {
+        assert_eq!(
+            root(&[
+                (B256::ZERO, B256::repeat_byte(1)),
+                (key(&[0x80], 0), B256::repeat_byte(2)),
+            ])?,
+            b256!("dfc69c94013a8b3c65395625a719a87534a7cfd38719251ad8c8ea7fe79f065e")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn one_stem_colocated_values() -> Result<()> {
+        let key_values = [3, 4, 9, 255].map(|i| (key(&[], i), B256::repeat_byte(i)));
+        assert_eq!(
+            root(&key_values)?,
+            b256!("2bed3fb591dfe32a28b5bab98d48c531207e12f52ed7a5a92cb7e444d7227bd9")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn two_stem_colocated_values() -> Result<()> {
+        assert_eq!(
+            root(&[
+                (key(&[], 3), B256::repeat_byte(1)),
+                (key(&[], 4), B256::repeat_byte(2)),
+                (key(&[0x80], 3), B256::repeat_byte(3)),
+                (key(&[0x80], 4), B256::repeat_byte(4)),
+            ])?,
+            b256!("9aa31099747e0f48bc98dc487c11e0b8490a6852641cffd83e57350f549407e9")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn two_keys_match_first_42_bits() -> Result<()> {
+        assert_eq!(
+            root(&[
+                (key(&[0, 0, 0, 0, 0, 0xc0], 0), B256::repeat_byte(1)),
+                (key(&[0, 0, 0, 0, 0, 0xe0], 0), B256::repeat_byte(2)),
+            ])?,
+            b256!("bc078216ce8975f8a5b571d42106787f572fb571a1357f2b15123c4cd47efa76")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn insert_duplicate_key() -> Result<()> {
+        let key = B256::repeat_byte(1);
+        let expected_root =
+            b256!("7f7c7ff309a43b4756fffedc777a79000eb487f080b978b00f72d6b55ae7164a");
+        assert_eq!(
+            root(&[(key, B256::repeat_byte(1)), (key, B256::repeat_byte(2))])?,
+            expected_root
+        );
+        assert_eq!(root(&[(key, B256::repeat_byte(2))])?, expected_root);
+        Ok(())
+    }
+
+    #[test]
+    fn large_number_of_entries() -> Result<()> {
+        let key_values = (0..=255u8)
+            .map(|i| (key(&[i], 0), B256::repeat_byte(0xff)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            root(&key_values)?,
+            b256!("9e897823c5e16f36d200257e5c054114388db3872c147c35b469c4341153afbd")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn accounts() -> Result<()> {
+        let mut tree = init();
+        let eoa = Address::from_str("0x3b7c4c2b2b25239e58f8e67509b32edb5bbf293c")?;
+        tree.create_eoa(eoa, U256::from(8832), 32)?;
+        assert_eq!(
+            tree.root()?,
+            b256!("27b349d28e51a12f03f15534fc41703bd31f87c67a49303998c5e5f02db225aa")
+        );
+
+        // PUSH32 at the end of the first chunk, so the second chunk starts with push data
+        let mut code = vec![0x5b; 30];
+        code.push(0x7f);
+        code.extend([0xff; 32]);
+        code.push(0x00);
+        let sc = Address::from_str("0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984")?;
+        tree.create_sc(sc, U256::from(100), 21, code)?;
+        tree.set_storage(sc, U256::ZERO, B256::with_last_byte(1))?;
+        tree.set_storage(sc, U256::from(1000), B256::with_last_byte(2))?;
+        assert_eq!(
+            tree.root()?,
+            b256!("cfe99459c0c47de3d9fb9e6b9b8859b84319ce7ae50866e2ade332aaced9fa37")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::errors::DbError;
+
+/// Encoding of the key, used by databases that store raw bytes.
+pub trait KeyEncoding: Sized {
+    fn encode_key(&self) -> Vec<u8>;
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, DbError>;
+}
+
+/// Encoding of the value, used by databases that store raw bytes.
+pub trait ValueEncoding: Sized {
+    fn encode_value(&self) -> Vec<u8>;
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DbError>;
+}
+
+impl KeyEncoding for Vec<u8> {
+    fn encode_key(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, DbError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> KeyEncoding for [u8; N] {
+    fn encode_key(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, DbError> {
+        Self::decode_value(bytes)
+    }
+}
+
+impl ValueEncoding for Vec<u8> {
+    fn encode_value(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DbError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> ValueEncoding for [u8; N] {
+    fn encode_value(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, DbError> {
+        Self::try_from(bytes).map_err(|_| {
+            DbError::Decoding(format!(
+                "expected {N} bytes, but found {} bytes",
+                bytes.len()
+            ))
+        })
+    }
+}
+
+#[cfg(feature = "alloy-primitives")]
+impl KeyEncoding for alloy_primitives::B256 {
+    fn encode_key(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, DbError> {
+        <[u8; 32]>::decode_value(bytes).map(Self::from)
+    }
+}
+
+#[cfg(feature = "banderwagon")]
+impl KeyEncoding for banderwagon::Element {
+    fn encode_key(&self) -> Vec<u8> {
+        use banderwagon::CanonicalSerialize;
+
+        let mut bytes = vec![];
+        self.serialize_compressed(&mut bytes)
+            .expect("Element should serialize into Vec");
+        bytes
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, DbError> {
+        use banderwagon::CanonicalDeserialize;
+
+        Self::deserialize_compressed(bytes).map_err(|err| DbError::Decoding(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok_eq};
+
+    use super::*;
+
+    #[test]
+    fn value_roundtrip() {
+        let value = vec![1u8, 2, 3];
+        assert_ok_eq!(Vec::<u8>::decode_value(&value.encode_value()), value);
+
+        let value = [1u8, 2, 3, 4];
+        assert_ok_eq!(<[u8; 4]>::decode_value(&value.encode_value()), value);
+    }
+
+    #[test]
+    fn key_roundtrip() {
+        let key = vec![1u8, 2, 3];
+        assert_ok_eq!(Vec::<u8>::decode_key(&key.encode_key()), key);
+
+        let key = [1u8, 2, 3, 4];
+        assert_ok_eq!(<[u8; 4]>::decode_key(&key.encode_key()), key);
+    }
+
+    #[test]
+    fn value_invalid_length() {
+        assert_err!(<[u8; 4]>::decode_value(&[1, 2, 3]));
+    }
+}
@@ -4,4 +4,11 @@ use thiserror::Error;
 pub enum DbError {
     #[error("General DB Error")]
     Error,
+    #[error("Error decoding value: {0}")]
+    Decoding(String),
+    #[error("Operation not supported by the DB: {0}")]
+    Unsupported(&'static str),
+    #[cfg(feature = "rocksdb")]
+    #[error("RocksDB Error: {0}")]
+    RocksDb(#[from] rocksdb::Error),
 }
@@ -1,10 +1,44 @@
 use errors::DbError;
+pub use write_batch::{WriteBatch, WriteOp};
 
+pub mod encoding;
 pub mod errors;
 pub mod memory_db;
+pub mod pruner;
+#[cfg(feature = "rocksdb")]
+pub mod rocks_db;
+mod write_batch;
 
 pub trait Db<K, V> {
     fn write(&mut self, key: K, value: V) -> Result<(), DbError>;
 
     fn read(&self, key: &K) -> Result<Option<V>, DbError>;
+
+    /// Deletes the value. Not supported by default.
+    fn delete(&mut self, _key: &K) -> Result<(), DbError> {
+        Err(DbError::Unsupported("delete"))
+    }
+
+    /// Reads the metadata, which is stored separately from the values. Not supported by default.
+    fn read_metadata(&self, _key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Err(DbError::Unsupported("metadata"))
+    }
+
+    /// Writes the metadata, or deletes it if the value is None. Not supported by default.
+    fn write_metadata(&mut self, _key: Vec<u8>, _value: Option<Vec<u8>>) -> Result<(), DbError> {
+        Err(DbError::Unsupported("metadata"))
+    }
+
+    /// Applies all operations from the batch. Implementations should apply them atomically, the
+    /// default implementation applies them one by one.
+    fn write_batch(&mut self, batch: WriteBatch<K, V>) -> Result<(), DbError> {
+        for op in batch {
+            match op {
+                WriteOp::Write(key, value) => self.write(key, value)?,
+                WriteOp::Delete(key) => self.delete(&key)?,
+                WriteOp::Metadata(key, value) => self.write_metadata(key, value)?,
+            }
+        }
+        Ok(())
+    }
 }
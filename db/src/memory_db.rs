@@ -1,16 +1,18 @@
-use super::{Db, DbError};
+use super::{Db, DbError, WriteBatch, WriteOp};
 
 use std::{collections::HashMap, hash::Hash};
 
 #[derive(Default)]
 pub struct MemoryDb<K, V> {
     data: HashMap<K, V>,
+    metadata: HashMap<Vec<u8>, Vec<u8>>,
 }
 
 impl<K, V> MemoryDb<K, V> {
     pub fn new() -> Self {
         MemoryDb {
             data: HashMap::new(),
+            metadata: HashMap::new(),
         }
     }
 }
@@ -24,13 +26,41 @@ impl<K: Hash + Eq, V: Clone> Db<K, V> for MemoryDb<K, V> {
     fn read(&self, key: &K) -> Result<Option<V>, DbError> {
         Ok(self.data.get(key).cloned())
     }
+
+    fn delete(&mut self, key: &K) -> Result<(), DbError> {
+        self.data.remove(key);
+        Ok(())
+    }
+
+    fn read_metadata(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self.metadata.get(key).cloned())
+    }
+
+    fn write_metadata(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<(), DbError> {
+        match value {
+            Some(value) => self.metadata.insert(key, value),
+            None => self.metadata.remove(&key),
+        };
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch<K, V>) -> Result<(), DbError> {
+        for op in batch {
+            match op {
+                WriteOp::Write(key, value) => self.write(key, value)?,
+                WriteOp::Delete(key) => self.delete(&key)?,
+                WriteOp::Metadata(key, value) => self.write_metadata(key, value)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use claim::{assert_ok, assert_ok_eq};
 
-    use super::{Db, MemoryDb};
+    use super::{Db, MemoryDb, WriteBatch};
 
     #[test]
     fn test_read_missing() {
@@ -61,4 +91,47 @@ mod tests {
         assert_ok!(memory_db.write(key, value2));
         assert_ok_eq!(memory_db.read(&key), Some(value2));
     }
+
+    #[test]
+    fn test_delete() {
+        let mut memory_db: MemoryDb<[u8; 4], [u16; 8]> = MemoryDb::new();
+        let key = [1u8, 2, 3, 4];
+        let value = [1u16, 1, 2, 3, 5, 8, 13, 21];
+
+        assert_ok!(memory_db.write(key, value));
+        assert_ok!(memory_db.delete(&key));
+        assert_ok_eq!(memory_db.read(&key), None);
+        // Deleting missing key is not an error
+        assert_ok!(memory_db.delete(&key));
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let mut memory_db: MemoryDb<[u8; 4], [u16; 8]> = MemoryDb::new();
+        let key1 = [1u8, 2, 3, 4];
+        let key2 = [5u8, 6, 7, 8];
+        let value1 = [0u16, 1, 1, 2, 3, 5, 8, 13];
+        let value2 = [1u16, 1, 2, 3, 5, 8, 13, 21];
+
+        let mut batch = WriteBatch::new();
+        batch.write(key1, value1);
+        batch.write(key2, value1);
+        batch.write(key2, value2);
+        assert_ok!(memory_db.write_batch(batch));
+
+        assert_ok_eq!(memory_db.read(&key1), Some(value1));
+        assert_ok_eq!(memory_db.read(&key2), Some(value2));
+
+        let mut batch = WriteBatch::new();
+        batch.delete(key1);
+        batch.write_metadata(key1.to_vec(), Some(vec![1]));
+        batch.write_metadata(key2.to_vec(), Some(vec![2]));
+        batch.write_metadata(key2.to_vec(), None);
+        assert_ok!(memory_db.write_batch(batch));
+
+        assert_ok_eq!(memory_db.read(&key1), None);
+        assert_ok_eq!(memory_db.read(&key2), Some(value2));
+        assert_ok_eq!(memory_db.read_metadata(&key1), Some(vec![1]));
+        assert_ok_eq!(memory_db.read_metadata(&key2), None);
+    }
 }
@@ -0,0 +1,360 @@
+use std::{collections::HashMap, hash::Hash, marker::PhantomData};
+
+use crate::{encoding::KeyEncoding, errors::DbError, Db, WriteBatch};
+
+const ROOTS_KEY: &[u8] = b"pruner/roots";
+const NODE_KEY_PREFIX: &[u8] = b"pruner/node/";
+
+/// Reference counts of trie nodes, used to delete nodes that are no longer reachable from the last
+/// few committed roots.
+///
+/// A node is referenced once by each stored node that has it as a child, and once by each retained
+/// root. Nodes are identified by their key, so the same node can be written more than once and
+/// still be stored only once.
+///
+/// Reference counts and retained roots are stored in the Db metadata, and are updated in the same
+/// batch as the nodes. Nodes that were stored before pruning was enabled are not tracked, unless
+/// they are written again.
+pub struct Pruner<K> {
+    retained_roots: usize,
+    _phantom: PhantomData<K>,
+}
+
+/// Reference count of the tracked node, together with its children, so the node doesn't have to be
+/// read when it's deleted.
+struct NodeRecord<K> {
+    ref_count: u64,
+    children: Vec<K>,
+}
+
+impl<K: KeyEncoding + Clone + Eq + Hash> Pruner<K> {
+    pub fn new(retained_roots: usize) -> Self {
+        assert!(retained_roots > 0, "At least one root should be retained");
+        Self {
+            retained_roots,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Roots that are currently retained, from the oldest to the newest.
+    pub fn roots<V>(&self, db: &dyn Db<K, V>) -> Result<Vec<K>, DbError> {
+        db.read_metadata(ROOTS_KEY)?
+            .map_or(Ok(vec![]), |bytes| decode_keys(&bytes))
+    }
+
+    /// Counts the references from the nodes in the batch and from the new root, releases the roots
+    /// that are no longer retained and adds the deletes of the nodes that are not referenced
+    /// anymore to the batch. Returns the roots that are not retained anymore.
+    ///
+    /// Must be called before the batch is written to the Db.
+    pub fn commit<V>(
+        &self,
+        batch: &mut WriteBatch<K, V>,
+        root: K,
+        db: &dyn Db<K, V>,
+        children: impl Fn(&V) -> Vec<K>,
+    ) -> Result<Vec<K>, DbError> {
+        let mut records = HashMap::new();
+
+        // Nodes have to be tracked before references are counted, as children can come after their
+        // parents in the batch
+        let mut new_nodes = vec![];
+        for (key, value) in batch.writes() {
+            if records.contains_key(key) {
+                continue;
+            }
+            let record = Self::read_record(db, key)?;
+            if record.is_none() {
+                new_nodes.push(key.clone());
+            }
+            records.insert(
+                key.clone(),
+                Some(record.unwrap_or_else(|| NodeRecord {
+                    ref_count: 0,
+                    children: children(value),
+                })),
+            );
+        }
+        for key in &new_nodes {
+            let node_children = records[key]
+                .as_ref()
+                .map_or(vec![], |record| record.children.clone());
+            for child in node_children {
+                Self::update_ref_count(&mut records, db, &child, 1)?;
+            }
+        }
+
+        let mut roots = self.roots(db)?;
+        if roots.last() != Some(&root) {
+            Self::update_ref_count(&mut records, db, &root, 1)?;
+            roots.push(root);
+        }
+
+        let released_roots = roots.len().saturating_sub(self.retained_roots);
+        let released = roots.drain(..released_roots).collect::<Vec<_>>();
+        let mut unreferenced = released.clone();
+        while let Some(key) = unreferenced.pop() {
+            if Self::update_ref_count(&mut records, db, &key, -1)? != Some(0) {
+                continue;
+            }
+            if let Some(record) = records.insert(key.clone(), None).flatten() {
+                unreferenced.extend(record.children);
+            }
+            batch.write_metadata(node_metadata_key(&key), None);
+            batch.delete(key);
+        }
+
+        for (key, record) in records {
+            if let Some(record) = record {
+                batch.write_metadata(node_metadata_key(&key), Some(record.encode()));
+            }
+        }
+        batch.write_metadata(ROOTS_KEY.to_vec(), Some(encode_keys(&roots)));
+        // The same root can be committed again later, so it can be released and still retained
+        Ok(released
+            .into_iter()
+            .filter(|root| !roots.contains(root))
+            .collect())
+    }
+
+    fn read_record<V>(db: &dyn Db<K, V>, key: &K) -> Result<Option<NodeRecord<K>>, DbError> {
+        db.read_metadata(&node_metadata_key(key))?
+            .map(|bytes| NodeRecord::decode(&bytes))
+            .transpose()
+    }
+
+    // Only references to tracked nodes are counted, as untracked nodes are never deleted anyway.
+    // Returns the new reference count, or None if the node is not tracked
+    fn update_ref_count<V>(
+        records: &mut HashMap<K, Option<NodeRecord<K>>>,
+        db: &dyn Db<K, V>,
+        key: &K,
+        delta: i64,
+    ) -> Result<Option<u64>, DbError> {
+        if !records.contains_key(key) {
+            records.insert(key.clone(), Self::read_record(db, key)?);
+        }
+        Ok(records.get_mut(key).and_then(Option::as_mut).map(|record| {
+            record.ref_count = record.ref_count.saturating_add_signed(delta);
+            record.ref_count
+        }))
+    }
+}
+
+impl<K: KeyEncoding> NodeRecord<K> {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.ref_count.to_be_bytes().to_vec();
+        bytes.extend(encode_keys(&self.children));
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DbError> {
+        let (ref_count, children) = split_bytes(bytes, 8)?;
+        Ok(Self {
+            ref_count: u64::from_be_bytes(ref_count.try_into().expect("Length is checked")),
+            children: decode_keys(children)?,
+        })
+    }
+}
+
+fn node_metadata_key<K: KeyEncoding>(key: &K) -> Vec<u8> {
+    [NODE_KEY_PREFIX, &key.encode_key()].concat()
+}
+
+// Keys are encoded one after another, each prefixed with its length
+fn encode_keys<K: KeyEncoding>(keys: &[K]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for key in keys {
+        let key = key.encode_key();
+        bytes.extend((key.len() as u32).to_be_bytes());
+        bytes.extend(key);
+    }
+    bytes
+}
+
+fn decode_keys<K: KeyEncoding>(mut bytes: &[u8]) -> Result<Vec<K>, DbError> {
+    let mut keys = vec![];
+    while !bytes.is_empty() {
+        let (len, rest) = split_bytes(bytes, 4)?;
+        let len = u32::from_be_bytes(len.try_into().expect("Length is checked"));
+        let (key, rest) = split_bytes(rest, len as usize)?;
+        keys.push(K::decode_key(key)?);
+        bytes = rest;
+    }
+    Ok(keys)
+}
+
+fn split_bytes(bytes: &[u8], mid: usize) -> Result<(&[u8], &[u8]), DbError> {
+    if bytes.len() < mid {
+        return Err(DbError::Decoding(format!(
+            "expected at least {mid} bytes, but found {} bytes",
+            bytes.len()
+        )));
+    }
+    Ok(bytes.split_at(mid))
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_ok, assert_ok_eq, assert_some};
+
+    use crate::{memory_db::MemoryDb, WriteOp};
+
+    use super::*;
+
+    impl KeyEncoding for u32 {
+        fn encode_key(&self) -> Vec<u8> {
+            self.to_be_bytes().to_vec()
+        }
+
+        fn decode_key(bytes: &[u8]) -> Result<Self, DbError> {
+            <[u8; 4]>::decode_key(bytes).map(u32::from_be_bytes)
+        }
+    }
+
+    // Returns the number of deleted nodes
+    fn commit(
+        pruner: &Pruner<u32>,
+        db: &mut MemoryDb<u32, Vec<u32>>,
+        nodes: Vec<(u32, Vec<u32>)>,
+        root: u32,
+    ) -> Result<usize, DbError> {
+        // Nodes of the test tries are lists of their children
+        let children = |node: &Vec<u32>| node.clone();
+
+        let mut batch = WriteBatch::new();
+        batch.extend(nodes);
+        pruner.commit(&mut batch, root, &*db, children)?;
+        let deleted = batch
+            .iter()
+            .filter(|op| matches!(op, WriteOp::Delete(_)))
+            .count();
+        db.write_batch(batch)?;
+        Ok(deleted)
+    }
+
+    #[test]
+    fn prune_replaced_nodes() -> Result<(), DbError> {
+        let pruner = Pruner::new(2);
+        let mut db = MemoryDb::new();
+
+        // 1 -> (2, 3)
+        assert_ok_eq!(
+            commit(
+                &pruner,
+                &mut db,
+                vec![(2, vec![]), (3, vec![]), (1, vec![2, 3])],
+                1
+            ),
+            0
+        );
+        // 4 -> (2, 5)
+        assert_ok_eq!(
+            commit(&pruner, &mut db, vec![(4, vec![2, 5]), (5, vec![])], 4),
+            0
+        );
+        // 6 -> (7, 5)
+        assert_ok_eq!(
+            commit(&pruner, &mut db, vec![(6, vec![7, 5]), (7, vec![])], 6),
+            2
+        );
+        assert_ok_eq!(pruner.roots(&db), vec![4, 6]);
+        for key in [1, 3] {
+            assert_none!(db.read(&key)?);
+        }
+        for key in [2, 4, 5, 6, 7] {
+            assert_some!(db.read(&key)?);
+        }
+
+        // Same root again doesn't release anything
+        assert_ok_eq!(commit(&pruner, &mut db, vec![], 6), 0);
+        assert_ok_eq!(commit(&pruner, &mut db, vec![], 7), 2);
+        for key in [2, 4] {
+            assert_none!(db.read(&key)?);
+        }
+        for key in [5, 6, 7] {
+            assert_some!(db.read(&key)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shared_nodes() -> Result<(), DbError> {
+        let pruner = Pruner::new(1);
+        let mut db = MemoryDb::new();
+
+        // Node 3 is referenced twice, and written twice
+        assert_ok!(commit(
+            &pruner,
+            &mut db,
+            vec![(3, vec![]), (2, vec![3]), (3, vec![]), (1, vec![2, 3])],
+            1
+        ));
+        // Node 3 is still referenced by node 2
+        assert_ok_eq!(commit(&pruner, &mut db, vec![(4, vec![2])], 4), 1);
+        assert_some!(db.read(&3)?);
+        assert_ok_eq!(commit(&pruner, &mut db, vec![(5, vec![])], 5), 3);
+        assert_none!(db.read(&3)?);
+        Ok(())
+    }
+
+    #[test]
+    fn untracked_nodes() -> Result<(), DbError> {
+        let pruner = Pruner::new(1);
+        let mut db = MemoryDb::new();
+        assert_ok!(db.write(2, vec![]));
+
+        assert_ok!(commit(&pruner, &mut db, vec![(1, vec![2])], 1));
+        // Node 2 was stored before it was seen by the pruner, so it's kept
+        assert_ok_eq!(commit(&pruner, &mut db, vec![(3, vec![])], 3), 1);
+        assert_some!(db.read(&2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn released_roots() -> Result<(), DbError> {
+        let pruner = Pruner::new(2);
+        let mut db = MemoryDb::new();
+        let mut commit = |root| {
+            let mut batch = WriteBatch::new();
+            batch.write(root, vec![]);
+            let released = pruner.commit(&mut batch, root, &db, |node| node.clone());
+            db.write_batch(batch)?;
+            released
+        };
+
+        assert_ok_eq!(commit(1), vec![]);
+        assert_ok_eq!(commit(2), vec![]);
+        // Root 1 is retained again
+        assert_ok_eq!(commit(1), vec![]);
+        assert_ok_eq!(commit(3), vec![2]);
+        assert_ok_eq!(commit(4), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    fn persisted_ref_counts() -> Result<(), DbError> {
+        let mut db = MemoryDb::new();
+
+        // 1 -> (2, 3)
+        assert_ok!(commit(
+            &Pruner::new(1),
+            &mut db,
+            vec![(2, vec![]), (3, vec![]), (1, vec![2, 3])],
+            1
+        ));
+
+        // New pruner continues from the state stored in the Db
+        let pruner = Pruner::new(1);
+        assert_ok_eq!(pruner.roots(&db), vec![1]);
+        // 4 -> (2)
+        assert_ok_eq!(commit(&pruner, &mut db, vec![(4, vec![2])], 4), 2);
+        for key in [1, 3] {
+            assert_none!(db.read(&key)?);
+        }
+        for key in [2, 4] {
+            assert_some!(db.read(&key)?);
+        }
+        Ok(())
+    }
+}
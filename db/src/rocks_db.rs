@@ -0,0 +1,202 @@
+use std::{marker::PhantomData, path::Path};
+
+use rocksdb::{ColumnFamily, Options, DB};
+
+use super::{
+    encoding::{KeyEncoding, ValueEncoding},
+    Db, DbError, WriteBatch, WriteOp,
+};
+
+const METADATA_COLUMN: &str = "metadata";
+
+/// Persistent database backed by RocksDB.
+pub struct RocksDb<K, V> {
+    db: DB,
+    _phantom: PhantomData<(K, V)>,
+}
+
+impl<K, V> RocksDb<K, V> {
+    /// Opens the database at the given path, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DbError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        Ok(Self {
+            db: DB::open_cf(&options, path, [METADATA_COLUMN])?,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn metadata_column(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(METADATA_COLUMN)
+            .expect("Metadata column family should be created on open")
+    }
+}
+
+impl<K: KeyEncoding, V: ValueEncoding> Db<K, V> for RocksDb<K, V> {
+    fn write(&mut self, key: K, value: V) -> Result<(), DbError> {
+        self.db.put(key.encode_key(), value.encode_value())?;
+        Ok(())
+    }
+
+    fn read(&self, key: &K) -> Result<Option<V>, DbError> {
+        self.db
+            .get_pinned(key.encode_key())?
+            .map(|bytes| V::decode_value(&bytes))
+            .transpose()
+    }
+
+    fn delete(&mut self, key: &K) -> Result<(), DbError> {
+        self.db.delete(key.encode_key())?;
+        Ok(())
+    }
+
+    fn read_metadata(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        Ok(self
+            .db
+            .get_pinned_cf(self.metadata_column(), key)?
+            .map(|bytes| bytes.to_vec()))
+    }
+
+    fn write_metadata(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<(), DbError> {
+        let column = self.metadata_column();
+        match value {
+            Some(value) => self.db.put_cf(column, key, value)?,
+            None => self.db.delete_cf(column, key)?,
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch<K, V>) -> Result<(), DbError> {
+        let column = self.metadata_column();
+        let mut rocks_batch = rocksdb::WriteBatch::default();
+        for op in batch {
+            match op {
+                WriteOp::Write(key, value) => {
+                    rocks_batch.put(key.encode_key(), value.encode_value())
+                }
+                WriteOp::Delete(key) => rocks_batch.delete(key.encode_key()),
+                WriteOp::Metadata(key, Some(value)) => rocks_batch.put_cf(column, key, value),
+                WriteOp::Metadata(key, None) => rocks_batch.delete_cf(column, key),
+            }
+        }
+        self.db.write(rocks_batch)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_ok, assert_ok_eq};
+    use tempfile::TempDir;
+
+    use super::{Db, DbError, RocksDb, WriteBatch};
+
+    fn open(dir: &TempDir) -> Result<RocksDb<[u8; 4], Vec<u8>>, DbError> {
+        RocksDb::open(dir.path())
+    }
+
+    #[test]
+    fn test_read_missing() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let rocks_db = open(&dir)?;
+        let key = [1u8, 2, 3, 4];
+        assert_ok_eq!(rocks_db.read(&key), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let mut rocks_db = open(&dir)?;
+        let key = [1u8, 2, 3, 4];
+        let value1 = vec![0u8, 1, 1, 2, 3, 5, 8, 13];
+        let value2 = vec![1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        assert_ok!(rocks_db.write(key, value1.clone()));
+        assert_ok_eq!(rocks_db.read(&key), Some(value1));
+
+        assert_ok!(rocks_db.write(key, value2.clone()));
+        assert_ok_eq!(rocks_db.read(&key), Some(value2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let mut rocks_db = open(&dir)?;
+        let key = [1u8, 2, 3, 4];
+        let value = vec![1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        assert_ok!(rocks_db.write(key, value));
+        assert_ok!(rocks_db.delete(&key));
+        assert_ok_eq!(rocks_db.read(&key), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let key = [1u8, 2, 3, 4];
+        let value = vec![1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        {
+            let mut rocks_db = open(&dir)?;
+            assert_ok!(rocks_db.write(key, value.clone()));
+        }
+
+        let rocks_db = open(&dir)?;
+        assert_ok_eq!(rocks_db.read(&key), Some(value));
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let key = [1u8, 2, 3, 4];
+        let value = vec![1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        {
+            let mut rocks_db = open(&dir)?;
+            assert_ok!(rocks_db.write_metadata(key.to_vec(), Some(value.clone())));
+            // Metadata doesn't share keys with the values
+            assert_ok_eq!(rocks_db.read(&key), None);
+        }
+
+        let mut rocks_db = open(&dir)?;
+        assert_ok_eq!(rocks_db.read_metadata(&key), Some(value));
+        assert_ok!(rocks_db.write_metadata(key.to_vec(), None));
+        assert_ok_eq!(rocks_db.read_metadata(&key), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_batch() -> Result<(), DbError> {
+        let dir = TempDir::new().unwrap();
+        let mut rocks_db = open(&dir)?;
+        let key1 = [1u8, 2, 3, 4];
+        let key2 = [5u8, 6, 7, 8];
+        let value1 = vec![0u8, 1, 1, 2, 3, 5, 8, 13];
+        let value2 = vec![1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        let mut batch = WriteBatch::new();
+        batch.write(key1, value1.clone());
+        batch.write(key2, value1.clone());
+        batch.write(key2, value2.clone());
+        assert_ok!(rocks_db.write_batch(batch));
+
+        assert_ok_eq!(rocks_db.read(&key1), Some(value1.clone()));
+        assert_ok_eq!(rocks_db.read(&key2), Some(value2.clone()));
+
+        let mut batch = WriteBatch::new();
+        batch.delete(key1);
+        batch.write_metadata(key2.to_vec(), Some(value1.clone()));
+        assert_ok!(rocks_db.write_batch(batch));
+
+        assert_ok_eq!(rocks_db.read(&key1), None);
+        assert_ok_eq!(rocks_db.read(&key2), Some(value2));
+        assert_ok_eq!(rocks_db.read_metadata(&key2), Some(value1));
+        Ok(())
+    }
+}
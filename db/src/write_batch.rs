@@ -0,0 +1,81 @@
+/// Single operation of the [WriteBatch].
+pub enum WriteOp<K, V> {
+    Write(K, V),
+    Delete(K),
+    /// Writes the metadata, or deletes it if the value is None. Metadata is stored separately from
+    /// the values.
+    Metadata(Vec<u8>, Option<Vec<u8>>),
+}
+
+/// Collection of writes that should be applied to the database together.
+pub struct WriteBatch<K, V> {
+    ops: Vec<WriteOp<K, V>>,
+}
+
+impl<K, V> WriteBatch<K, V> {
+    pub fn new() -> Self {
+        Self { ops: vec![] }
+    }
+
+    pub fn write(&mut self, key: K, value: V) {
+        self.ops.push(WriteOp::Write(key, value));
+    }
+
+    pub fn delete(&mut self, key: K) {
+        self.ops.push(WriteOp::Delete(key));
+    }
+
+    pub fn write_metadata(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        self.ops.push(WriteOp::Metadata(key, value));
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, WriteOp<K, V>> {
+        self.ops.iter()
+    }
+
+    /// Returns the written `(key, value)` pairs, in the order they were added.
+    pub fn writes(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.ops.iter().filter_map(|op| match op {
+            WriteOp::Write(key, value) => Some((key, value)),
+            _ => None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl<K, V> Default for WriteBatch<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Extend<(K, V)> for WriteBatch<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.ops.extend(
+            iter.into_iter()
+                .map(|(key, value)| WriteOp::Write(key, value)),
+        )
+    }
+}
+
+impl<K, V> Extend<WriteOp<K, V>> for WriteBatch<K, V> {
+    fn extend<T: IntoIterator<Item = WriteOp<K, V>>>(&mut self, iter: T) {
+        self.ops.extend(iter)
+    }
+}
+
+impl<K, V> IntoIterator for WriteBatch<K, V> {
+    type Item = WriteOp<K, V>;
+    type IntoIter = std::vec::IntoIter<WriteOp<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
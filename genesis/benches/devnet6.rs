@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use alloy_primitives::{Address, U256};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use db::memory_db::MemoryDb;
+use genesis::Genesis;
+use verkle::{
+    storage::{AccountStorageLayout, HeaderLayout, PedersenKeyHasher, DEFAULT_KEY_CACHE_CAPACITY},
+    Trie,
+};
+
+const GENESIS_FILEPATH: &str = "../verkle/assets/devnet6_genesis.json";
+
+fn trie(key_cache_capacity: usize) -> Trie {
+    let mut trie = Trie::new(Box::new(MemoryDb::new()));
+    trie.set_key_hasher(PedersenKeyHasher::new(key_cache_capacity));
+    trie
+}
+
+fn import(c: &mut Criterion) {
+    let genesis = Genesis::from_file(GENESIS_FILEPATH).expect("Genesis should be readable");
+
+    let mut group = c.benchmark_group("devnet6_import");
+    group.sample_size(10);
+    for (name, key_cache_capacity) in [
+        ("uncached_keys", 0),
+        ("cached_keys", DEFAULT_KEY_CACHE_CAPACITY),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || trie(key_cache_capacity),
+                |mut trie| {
+                    genesis
+                        .populate(&mut trie)
+                        .expect("Genesis should be imported")
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn storage_slot_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage_slot_keys");
+    for (name, key_cache_capacity) in [
+        ("uncached_keys", 0),
+        ("cached_keys", DEFAULT_KEY_CACHE_CAPACITY),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    AccountStorageLayout::new_with_key_hasher(
+                        Address::repeat_byte(1),
+                        HeaderLayout::Separate,
+                        Arc::new(PedersenKeyHasher::new(key_cache_capacity)),
+                    )
+                },
+                // Main storage slots, 256 slots per stem
+                |storage| {
+                    for slot in 0..1024 {
+                        storage.storage_slot_key(U256::from(1000 + slot));
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, import, storage_slot_keys);
+criterion_main!(benches);
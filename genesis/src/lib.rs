@@ -0,0 +1,95 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use alloy_primitives::{Address, Bytes, B256, U256, U64};
+use anyhow::Result;
+use merkle::{account::AccountState, mpt::Mpt};
+use serde::Deserialize;
+use verkle::Trie;
+
+/// The genesis configuration, in the format used by geth's `genesis.json`.
+///
+/// Only the genesis allocation is parsed, other fields are ignored.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Genesis {
+    pub alloc: HashMap<Address, GenesisAccount>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct GenesisAccount {
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: Option<U64>,
+    #[serde(default)]
+    pub code: Option<Bytes>,
+    #[serde(default)]
+    pub storage: HashMap<U256, B256>,
+}
+
+impl GenesisAccount {
+    pub fn nonce(&self) -> u64 {
+        self.nonce.unwrap_or_default().to::<u64>()
+    }
+}
+
+impl Genesis {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Inserts all genesis accounts into the state and returns the resulting state root.
+    pub fn populate(&self, state: &mut impl GenesisState) -> Result<B256> {
+        for (address, account) in &self.alloc {
+            state.insert_genesis_account(*address, account)?;
+        }
+        state.state_root()
+    }
+}
+
+/// The state that can be populated from the genesis configuration.
+pub trait GenesisState {
+    fn insert_genesis_account(&mut self, address: Address, account: &GenesisAccount) -> Result<()>;
+
+    fn state_root(&mut self) -> Result<B256>;
+}
+
+impl GenesisState for Mpt {
+    fn insert_genesis_account(&mut self, address: Address, account: &GenesisAccount) -> Result<()> {
+        let mut account_state = AccountState::new_eoa(account.balance);
+        account_state.nonce = account.nonce();
+        self.set_account(address, &account_state)?;
+
+        if let Some(code) = &account.code {
+            self.set_code(address, code.to_vec())?;
+        }
+        for (key, value) in &account.storage {
+            self.set_storage(address, B256::from(*key), U256::from_be_bytes(value.0))?;
+        }
+        Ok(())
+    }
+
+    fn state_root(&mut self) -> Result<B256> {
+        self.get_hash()
+    }
+}
+
+impl GenesisState for Trie {
+    fn insert_genesis_account(&mut self, address: Address, account: &GenesisAccount) -> Result<()> {
+        let Some(code) = &account.code else {
+            return self.create_eoa(address, account.balance, account.nonce());
+        };
+        self.create_sc(address, account.balance, account.nonce(), code.to_vec())?;
+
+        let storage_layout = self.account_storage(address);
+        self.insert_batch(account.storage.iter().map(|(key, value)| {
+            (
+                storage_layout.storage_slot_key(*key),
+                U256::from_le_slice(value.as_slice()),
+            )
+        }))
+    }
+
+    fn state_root(&mut self) -> Result<B256> {
+        self.root()
+    }
+}
@@ -0,0 +1,23 @@
+#[cfg(test)]
+mod devnet6 {
+    use std::str::FromStr;
+
+    use alloy_primitives::B256;
+    use anyhow::Result;
+    use db::memory_db::MemoryDb;
+    use genesis::Genesis;
+    use verkle::Trie;
+
+    const GENESIS_FILEPATH: &str = "../verkle/assets/devnet6_genesis.json";
+    const STATE_ROOT: &str = "0x1fbf85345a3cbba9a6d44f991b721e55620a22397c2a93ee8d5011136ac300ee";
+
+    #[test]
+    fn state_root() -> Result<()> {
+        let genesis = Genesis::from_file(GENESIS_FILEPATH)?;
+        let mut trie = Trie::new(Box::new(MemoryDb::new()));
+
+        assert_eq!(genesis.populate(&mut trie)?, B256::from_str(STATE_ROOT)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod mainnet {
+    use alloy_primitives::{Address, Bytes, B256, U256};
+    use anyhow::Result;
+    use genesis::Genesis;
+    use merkle::mpt::Mpt;
+    use serde::Deserialize;
+
+    const HISTORY_FILEPATH: &str = "../history.json";
+
+    #[derive(Deserialize)]
+    struct BlockDeposits {
+        state_root: B256,
+        deposits: Vec<(Address, U256)>,
+    }
+
+    #[derive(Deserialize)]
+    struct HistoricalDeposits {
+        blocks: Vec<BlockDeposits>,
+    }
+
+    #[test]
+    fn state_root() -> Result<()> {
+        let history: HistoricalDeposits =
+            serde_json::from_reader(std::fs::File::open(HISTORY_FILEPATH)?)?;
+        let genesis_block = &history.blocks[0];
+
+        let mut genesis = Genesis::default();
+        for (address, amount) in &genesis_block.deposits {
+            genesis.alloc.entry(*address).or_default().balance += amount;
+        }
+
+        let mut mpt = Mpt::default();
+        assert_eq!(genesis.populate(&mut mpt)?, genesis_block.state_root);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_alloc() -> Result<()> {
+        let genesis: Genesis = serde_json::from_str(
+            r#"{
+                "config": { "chainId": 1 },
+                "alloc": {
+                    "0x0000000000000000000000000000000000000001": { "balance": "0x1" },
+                    "0x0000000000000000000000000000000000000002": {
+                        "balance": "0x0",
+                        "nonce": "0x1",
+                        "code": "0x6000",
+                        "storage": { "0x0": "0x0000000000000000000000000000000000000000000000000000000000000001" }
+                    }
+                }
+            }"#,
+        )?;
+        let account = &genesis.alloc[&Address::with_last_byte(2)];
+        assert_eq!(account.nonce(), 1);
+        assert_eq!(account.code, Some(Bytes::from_static(&[0x60, 0x00])));
+        assert_eq!(account.storage[&U256::ZERO], B256::with_last_byte(1));
+
+        let mut mpt = Mpt::default();
+        genesis.populate(&mut mpt)?;
+        assert_eq!(
+            mpt.get_storage(&Address::with_last_byte(2), B256::ZERO)?,
+            U256::from(1)
+        );
+        assert_eq!(
+            mpt.get_code(&Address::with_last_byte(2))?,
+            Some(vec![0x60, 0x00])
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,14 @@
-use alloy_primitives::{keccak256, B256, U256};
+use alloy_primitives::{b256, B256, U256};
 use alloy_rlp::{RlpDecodable, RlpEncodable};
 
+/// The root of the empty trie: `keccak256(rlp(""))`.
+pub const EMPTY_ROOT_HASH: B256 =
+    b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// The hash of the empty code: `keccak256([])`.
+pub const EMPTY_CODE_HASH: B256 =
+    b256!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+
 #[derive(Clone, RlpEncodable, RlpDecodable)]
 pub struct AccountState {
     pub nonce: u64,
@@ -14,8 +22,8 @@ impl AccountState {
         Self {
             nonce: 0,
             balance,
-            storage_root: keccak256([alloy_rlp::EMPTY_STRING_CODE]),
-            code_hash: keccak256([]),
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: EMPTY_CODE_HASH,
         }
     }
 }
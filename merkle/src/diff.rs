@@ -0,0 +1,233 @@
+use alloy_primitives::B256;
+use alloy_rlp::Decodable;
+use anyhow::{bail, Result};
+
+use crate::{
+    account::EMPTY_ROOT_HASH,
+    nibbles::{Nibble, Nibbles},
+    nodes::{BranchNode, ExtensionNode, LeafNode, Node},
+    Db,
+};
+
+/// The change of the value stored at some path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueDiff {
+    Added(Vec<u8>),
+    Removed(Vec<u8>),
+    Changed { old: Vec<u8>, new: Vec<u8> },
+}
+
+/// Returns all changes between the tries with the given roots, in the ascending order of paths.
+///
+/// Both tries are walked together and subtrees with the same hash are skipped, so only nodes on
+/// the paths to the changed values are loaded from the Db.
+pub fn diff(old_root: B256, new_root: B256, db: &Db) -> Result<Vec<(Nibbles, ValueDiff)>> {
+    let mut result = vec![];
+    diff_nodes(
+        &mut vec![],
+        root_node(old_root),
+        root_node(new_root),
+        db,
+        &mut result,
+    )?;
+    Ok(result)
+}
+
+fn root_node(root: B256) -> Node {
+    if root == EMPTY_ROOT_HASH {
+        Node::Nil
+    } else {
+        Node::Hash(root.into())
+    }
+}
+
+fn diff_nodes(
+    path: &mut Vec<Nibble>,
+    old: Node,
+    new: Node,
+    db: &Db,
+    result: &mut Vec<(Nibbles, ValueDiff)>,
+) -> Result<()> {
+    match (&old, &new) {
+        (Node::Nil, Node::Nil) => return Ok(()),
+        (Node::Hash(old_hash), Node::Hash(new_hash)) if **old_hash == **new_hash => return Ok(()),
+        _ => {}
+    }
+
+    let old = expand(old, db)?;
+    let new = expand(new, db)?;
+
+    let value_diff = match (old.value.is_empty(), new.value.is_empty()) {
+        (true, true) => None,
+        (true, false) => Some(ValueDiff::Added(new.value)),
+        (false, true) => Some(ValueDiff::Removed(old.value)),
+        (false, false) => (old.value != new.value).then_some(ValueDiff::Changed {
+            old: old.value,
+            new: new.value,
+        }),
+    };
+    if let Some(value_diff) = value_diff {
+        result.push((Nibbles::from_slice(&path), value_diff));
+    }
+
+    for (index, (old_child, new_child)) in old.children.into_iter().zip(new.children).enumerate() {
+        path.push(Nibble::try_from(index as u8).expect("Branch has 16 children"));
+        diff_nodes(path, old_child, new_child, db, result)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Represents the node as a branch node, by splitting the first nibble of the leaf or extension
+/// prefix into the branch child.
+fn expand(node: Node, db: &Db) -> Result<Box<BranchNode>> {
+    let branch_node = match node {
+        Node::Nil => BranchNode::default(),
+        Node::Leaf(leaf_node) => match leaf_node.prefix.split_first() {
+            Some((first, rest)) => BranchNode::new_with_child(
+                **first as usize,
+                Node::Leaf(LeafNode::new(Nibbles::from_slice(rest), leaf_node.value)),
+            ),
+            None => BranchNode::new_with_value(leaf_node.value),
+        },
+        Node::Extension(extension_node) => {
+            let ExtensionNode { prefix, node } = *extension_node;
+            let (first, rest) = prefix
+                .split_first()
+                .expect("Extension node can't have empty prefix");
+            let child = if rest.is_empty() {
+                node
+            } else {
+                Node::Extension(ExtensionNode::new(Nibbles::from_slice(rest), node).into())
+            };
+            BranchNode::new_with_child(**first as usize, child)
+        }
+        Node::Branch(branch_node) => return Ok(branch_node),
+        Node::Hash(hash_node) => {
+            let Some(encoded_node) = db.read(&hash_node)? else {
+                bail!("Node missing from Db: {:?}", *hash_node)
+            };
+            return expand(Node::decode(&mut encoded_node.as_slice())?, db);
+        }
+    };
+    Ok(branch_node.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use alloy_primitives::keccak256;
+    use db::{memory_db::MemoryDb, Db};
+
+    use crate::mpt::Mpt;
+
+    use super::*;
+
+    type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    fn expected_diff(old: &Entries, new: &Entries) -> Vec<(Vec<u8>, ValueDiff)> {
+        let mut paths = old.keys().chain(new.keys()).collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let value_diff = match (old.get(path), new.get(path)) {
+                    (None, Some(new)) => ValueDiff::Added(new.clone()),
+                    (Some(old), None) => ValueDiff::Removed(old.clone()),
+                    (Some(old), Some(new)) if old != new => ValueDiff::Changed {
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                    _ => return None,
+                };
+                Some((path.clone(), value_diff))
+            })
+            .collect()
+    }
+
+    fn packed_diff(
+        tree: &Mpt,
+        old_root: B256,
+        new_root: B256,
+    ) -> Result<Vec<(Vec<u8>, ValueDiff)>> {
+        Ok(tree
+            .diff(old_root, new_root)?
+            .into_iter()
+            .map(|(path, value_diff)| (path.to_packed().expect("Paths are bytes"), value_diff))
+            .collect())
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let mut tree = Mpt::default();
+        let root = tree.get_hash()?;
+        assert_eq!(tree.diff(root, root)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn random_changes() -> Result<()> {
+        let mut tree = Mpt::default();
+        let mut entries = Entries::new();
+        let mut roots = vec![(tree.get_hash()?, entries.clone())];
+
+        for round in 0u8..5 {
+            for i in 0u8..100 {
+                let path = keccak256([i % (20 + round * 20)])[..1 + i as usize % 3].to_vec();
+                let nibbles = Nibbles::from_packed(&path);
+                if (i + round) % 4 == 0 {
+                    tree.remove_raw(&nibbles)?;
+                    entries.remove(&path);
+                } else {
+                    let value = vec![round; 1 + i as usize % 40];
+                    tree.set_raw(&nibbles, value.clone())?;
+                    entries.insert(path, value);
+                }
+            }
+            roots.push((tree.get_hash()?, entries.clone()));
+        }
+
+        for (old_root, old_entries) in &roots {
+            for (new_root, new_entries) in &roots {
+                assert_eq!(
+                    packed_diff(&tree, *old_root, *new_root)?,
+                    expected_diff(old_entries, new_entries)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn same_subtrees_are_skipped() -> Result<()> {
+        let mut tree = Mpt::default();
+        for i in 0u8..100 {
+            tree.set_raw(&Nibbles::from_packed(keccak256([i])), vec![i; 40])?;
+        }
+        let changed_path = Nibbles::from_packed(keccak256([0]));
+        let old_root = tree.get_hash()?;
+        let mut proof = tree.get_proof(&changed_path)?;
+        tree.set_raw(&changed_path, vec![0xff])?;
+        let new_root = tree.get_hash()?;
+        proof.extend(tree.get_proof(&changed_path)?);
+
+        // Only nodes on the path to the changed value are needed, other subtrees are the same
+        let mut db = MemoryDb::new();
+        for node in proof {
+            db.write(keccak256(&node), node)?;
+        }
+        assert_eq!(
+            diff(old_root, new_root, &db)?,
+            vec![(
+                changed_path,
+                ValueDiff::Changed {
+                    old: vec![0; 40],
+                    new: vec![0xff]
+                }
+            )]
+        );
+        Ok(())
+    }
+}
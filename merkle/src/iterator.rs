@@ -0,0 +1,284 @@
+use alloy_primitives::B256;
+use alloy_rlp::Decodable;
+use anyhow::{bail, Result};
+
+use crate::{
+    nibbles::{Nibble, Nibbles},
+    nodes::{BranchNode, Node},
+    Db,
+};
+
+enum NodeRef<'a> {
+    Borrowed(&'a Node),
+    /// Node loaded from the Db.
+    Owned(Node),
+}
+
+/// Iterates over `(path, value)` pairs of the trie, in the ascending order of paths.
+///
+/// Hash nodes are loaded from the Db lazily, only when iteration reaches them.
+pub struct MptIterator<'a> {
+    root: &'a Node,
+    db: &'a Db,
+    stack: Vec<(Vec<Nibble>, NodeRef<'a>)>,
+    start: Vec<Nibble>,
+    end: Option<Vec<Nibble>>,
+}
+
+impl<'a> MptIterator<'a> {
+    pub(crate) fn new(root: &'a Node, db: &'a Db) -> Self {
+        Self {
+            root,
+            db,
+            stack: vec![(vec![], NodeRef::Borrowed(root))],
+            start: vec![],
+            end: None,
+        }
+    }
+
+    /// Restarts the iteration from the first path that is not smaller than `start`.
+    pub fn seek(&mut self, start: &[Nibble]) {
+        self.start = start.to_vec();
+        self.stack = vec![];
+        self.push(vec![], NodeRef::Borrowed(self.root));
+    }
+
+    /// Stops the iteration before the first path that is not smaller than `end`.
+    pub fn with_end(mut self, end: &[Nibble]) -> Self {
+        self.end = Some(end.to_vec());
+        self
+    }
+
+    fn push(&mut self, path: Vec<Nibble>, node: NodeRef<'a>) {
+        if matches!(
+            node,
+            NodeRef::Borrowed(Node::Nil) | NodeRef::Owned(Node::Nil)
+        ) {
+            return;
+        }
+        // All paths in the subtree are smaller than start
+        if path < self.start && !self.start.starts_with(&path) {
+            return;
+        }
+        // All paths in the subtree are not smaller than end
+        if self.end.as_ref().is_some_and(|end| path >= *end) {
+            return;
+        }
+        self.stack.push((path, node));
+    }
+
+    fn push_children(
+        &mut self,
+        path: &[Nibble],
+        children: impl DoubleEndedIterator<Item = NodeRef<'a>> + ExactSizeIterator,
+    ) {
+        // Pushed in reverse order, so that the first child is processed first
+        for (index, child) in children.enumerate().rev() {
+            let mut child_path = path.to_vec();
+            child_path.push(Nibble::try_from(index as u8).expect("Branch has 16 children"));
+            self.push(child_path, child);
+        }
+    }
+
+    fn load(&self, hash: &B256) -> Result<Node> {
+        let Some(encoded_node) = self.db.read(hash)? else {
+            bail!("Node missing from Db: {hash:?}")
+        };
+        Ok(Node::decode(&mut encoded_node.as_slice())?)
+    }
+
+    /// Pushes the children of the node to the stack, and returns the value stored in the node.
+    fn expand(
+        &mut self,
+        path: Vec<Nibble>,
+        node: NodeRef<'a>,
+    ) -> Result<Option<(Vec<Nibble>, Vec<u8>)>> {
+        let entry = match node {
+            NodeRef::Borrowed(node) => match node {
+                Node::Nil => None,
+                Node::Leaf(leaf_node) => Some((
+                    [path.as_slice(), &leaf_node.prefix].concat(),
+                    leaf_node.value.clone(),
+                )),
+                Node::Extension(extension_node) => {
+                    let child_path = [path.as_slice(), &extension_node.prefix].concat();
+                    self.push(child_path, NodeRef::Borrowed(&extension_node.node));
+                    None
+                }
+                Node::Branch(branch_node) => {
+                    self.push_children(&path, branch_node.children.iter().map(NodeRef::Borrowed));
+                    Some((path, branch_node.value.clone()))
+                }
+                Node::Hash(hash_node) => {
+                    let node = self.load(hash_node)?;
+                    self.push(path, NodeRef::Owned(node));
+                    None
+                }
+            },
+            NodeRef::Owned(node) => match node {
+                Node::Nil => None,
+                Node::Leaf(leaf_node) => Some((
+                    [path.as_slice(), &leaf_node.prefix].concat(),
+                    leaf_node.value,
+                )),
+                Node::Extension(extension_node) => {
+                    let child_path = [path.as_slice(), &extension_node.prefix].concat();
+                    self.push(child_path, NodeRef::Owned(extension_node.node));
+                    None
+                }
+                Node::Branch(branch_node) => {
+                    let BranchNode { children, value } = *branch_node;
+                    self.push_children(&path, children.into_iter().map(NodeRef::Owned));
+                    Some((path, value))
+                }
+                Node::Hash(hash_node) => {
+                    let node = self.load(&hash_node)?;
+                    self.push(path, NodeRef::Owned(node));
+                    None
+                }
+            },
+        };
+        // Branch node without value
+        Ok(entry.filter(|(_path, value)| !value.is_empty()))
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(Nibbles, Vec<u8>)>> {
+        while let Some((path, node)) = self.stack.pop() {
+            let Some((path, value)) = self.expand(path, node)? else {
+                continue;
+            };
+            if self.end.as_ref().is_some_and(|end| path >= *end) {
+                self.stack.clear();
+                return Ok(None);
+            }
+            if path >= self.start {
+                return Ok(Some((Nibbles::from_slice(path), value)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for MptIterator<'_> {
+    type Item = Result<(Nibbles, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(err) => {
+                self.stack.clear();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use alloy_primitives::keccak256;
+
+    use crate::mpt::Mpt;
+
+    use super::*;
+
+    type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    fn init(count: u8) -> Result<(Mpt, Entries)> {
+        let mut tree = Mpt::default();
+        let mut expected = BTreeMap::new();
+        for i in 0..count {
+            let path = keccak256([i])[..1 + i as usize % 4].to_vec();
+            let value = vec![i; 1 + i as usize];
+            tree.set_raw(&Nibbles::from_packed(&path), value.clone())?;
+            expected.insert(path, value);
+        }
+        Ok((tree, expected))
+    }
+
+    fn collect(iterator: MptIterator) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        iterator
+            .map(|entry| {
+                let (path, value) = entry?;
+                let packed = path
+                    .chunks(2)
+                    .map(|pair| Nibble::join(pair[0], pair[1]))
+                    .collect();
+                Ok((packed, value))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let tree = Mpt::default();
+        assert!(collect(tree.iter())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_all() -> Result<()> {
+        let (mut tree, expected) = init(100)?;
+        let expected = expected.into_iter().collect::<Vec<_>>();
+
+        assert_eq!(collect(tree.iter())?, expected);
+        // After commit, nodes are loaded from the Db
+        tree.get_hash()?;
+        assert_eq!(collect(tree.iter())?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn branch_value() -> Result<()> {
+        let mut tree = Mpt::default();
+        for path in [
+            b"do".to_vec(),
+            b"dog".to_vec(),
+            b"doge".to_vec(),
+            b"horse".to_vec(),
+        ] {
+            tree.set_raw(&Nibbles::from_packed(&path), path.clone())?;
+        }
+        tree.get_hash()?;
+
+        let paths = collect(tree.iter())?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                b"do".to_vec(),
+                b"dog".to_vec(),
+                b"doge".to_vec(),
+                b"horse".to_vec()
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn seek_and_end() -> Result<()> {
+        let (mut tree, expected) = init(100)?;
+        tree.get_hash()?;
+
+        let keys = expected.keys().cloned().collect::<Vec<_>>();
+        for (start, end) in [
+            (keys[10].clone(), keys[20].clone()),
+            (vec![0x00], vec![0x80]),
+            (vec![0x80], vec![0xff, 0xff]),
+            (keys[50][..1].to_vec(), keys[50].clone()),
+            (keys[30].clone(), keys[30].clone()),
+        ] {
+            let mut iterator = tree.iter().with_end(&Nibbles::from_packed(&end));
+            iterator.seek(&Nibbles::from_packed(&start));
+            let expected_range = expected
+                .range(start..end)
+                .map(|(path, value)| (path.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(collect(iterator)?, expected_range);
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
 use alloy_primitives::B256;
 
 pub mod account;
+pub mod diff;
+pub mod iterator;
 pub mod mpt;
 pub mod nibbles;
 pub mod nodes;
+pub mod proof;
+pub mod range_proof;
+pub mod stack_trie;
 
 type Db = dyn db::Db<B256, Vec<u8>>;
+type WriteBatch = db::WriteBatch<B256, Vec<u8>>;
@@ -1,26 +1,190 @@
-use alloy_primitives::{keccak256, Address, B256};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256, U64};
 use alloy_rlp::Decodable;
 use anyhow::{bail, Result};
-use db::memory_db::MemoryDb;
+use db::{memory_db::MemoryDb, pruner::Pruner};
 
 use crate::{
-    account::AccountState,
+    account::{AccountState, EMPTY_CODE_HASH, EMPTY_ROOT_HASH},
+    diff::{diff, ValueDiff},
+    iterator::MptIterator,
     nibbles::{Nibble, Nibbles},
     nodes::{Node, NodeTraversalInfo},
-    Db,
+    proof::{AccountProof, StorageProof},
+    range_proof::RangeProof,
+    Db, WriteBatch,
 };
 
 pub struct Mpt {
     root: Node,
     db: Box<Db>,
+    /// Roots of the storage tries that were modified since the last commit.
+    storage_tries: HashMap<Address, Node>,
+    /// Contract code (keyed by its hash) that was set since the last commit.
+    pending_code: HashMap<B256, Vec<u8>>,
+    pruner: Option<Pruner<B256>>,
+    /// Roots committed with [Mpt::commit], by block number.
+    committed_roots: BTreeMap<u64, B256>,
 }
 
 impl Mpt {
+    pub fn new(db: Box<Db>) -> Self {
+        Self {
+            root: Node::Nil,
+            db,
+            storage_tries: HashMap::new(),
+            pending_code: HashMap::new(),
+            pruner: None,
+            committed_roots: BTreeMap::new(),
+        }
+    }
+
+    pub fn new_with_root(root: B256, db: Box<Db>) -> Self {
+        let mut mpt = Self::new(db);
+        mpt.root = Self::root_node(root);
+        mpt
+    }
+
+    fn root_node(root: B256) -> Node {
+        if root == EMPTY_ROOT_HASH {
+            Node::Nil
+        } else {
+            Node::Hash(root.into())
+        }
+    }
+
+    /// Commits the trie as the state after the given block and returns its root.
+    ///
+    /// Committing a block that was already committed is handled as a reorg, so the roots of the
+    /// later blocks are dropped.
+    pub fn commit(&mut self, block_number: u64) -> Result<B256> {
+        let root = self.get_hash()?;
+        self.committed_roots.split_off(&block_number);
+        self.committed_roots.insert(block_number, root);
+        Ok(root)
+    }
+
+    /// Returns the root committed for the given block.
+    pub fn root_at(&self, block_number: u64) -> Option<B256> {
+        self.committed_roots.get(&block_number).copied()
+    }
+
+    /// Re-opens the trie at the given root, which must be stored in the Db. Uncommitted changes
+    /// are discarded.
+    pub fn checkout(&mut self, root: B256) -> Result<()> {
+        if root != EMPTY_ROOT_HASH && self.db.read(&root)?.is_none() {
+            bail!("Root missing from Db: {root:?}")
+        }
+        self.root = Self::root_node(root);
+        self.storage_tries.clear();
+        self.pending_code.clear();
+        Ok(())
+    }
+
+    /// Commits the trie and returns its root.
+    ///
+    /// Modified storage tries are committed first and `storage_root` of their accounts is updated.
     pub fn get_hash(&mut self) -> Result<B256> {
-        self.root.write(&mut *self.db)?;
-        match &self.root {
-            Node::Hash(hash) => Ok(*hash.clone()),
-            _ => Ok(keccak256(self.root.write(&mut *self.db)?)),
+        let mut batch = WriteBatch::new();
+        let storage_roots = self
+            .storage_tries
+            .iter()
+            .map(|(address, storage_trie)| (*address, Self::write_root(storage_trie, &mut batch)))
+            .collect::<Vec<_>>();
+        for (address, storage_root) in storage_roots {
+            let mut account = match self.get_account(&address)? {
+                Some(account) => account,
+                // Empty storage doesn't create the account (EIP-161)
+                None if storage_root == EMPTY_ROOT_HASH => continue,
+                None => AccountState::default(),
+            };
+            account.storage_root = storage_root;
+            self.set_account(address, &account)?;
+        }
+        let hash = Self::write_root(&self.root, &mut batch);
+
+        let pruned_roots = match &self.pruner {
+            Some(pruner) => pruner.commit(&mut batch, hash, &*self.db, |encoded| {
+                Self::referenced_nodes(encoded)
+            })?,
+            None => vec![],
+        };
+        // Code is not tracked by the pruner, so it's never deleted
+        batch.extend(
+            self.pending_code
+                .iter()
+                .map(|(hash, code)| (*hash, code.clone())),
+        );
+        self.db.write_batch(batch)?;
+        // Modified nodes are only dropped once they are stored, so a failed write can be retried
+        self.root = Self::root_node(hash);
+        self.storage_tries.clear();
+        self.pending_code.clear();
+        self.committed_roots
+            .retain(|_, root| !pruned_roots.contains(root));
+        Ok(hash)
+    }
+
+    /// Enables pruning of the nodes that are not reachable from the last `retained_roots` roots
+    /// returned by [Mpt::get_hash]. Pruned roots are also dropped from the committed roots.
+    ///
+    /// Pruning state is stored in the Db, so it should be enabled again when the Db is reopened.
+    /// Nodes that were stored before pruning was first enabled are only deleted if they are
+    /// written again, so pruning should be enabled before the trie is committed for the first
+    /// time.
+    pub fn enable_pruning(&mut self, retained_roots: usize) {
+        self.pruner = Some(Pruner::new(retained_roots));
+    }
+
+    /// Returns hashes of the nodes referenced by the encoded node, including the roots of storage
+    /// tries of the accounts stored in it.
+    fn referenced_nodes(encoded_node: &[u8]) -> Vec<B256> {
+        fn collect(node: &Node, hashes: &mut Vec<B256>) {
+            match node {
+                Node::Nil => {}
+                Node::Leaf(leaf_node) => {
+                    if let Ok(account) = AccountState::decode(&mut leaf_node.value.as_slice()) {
+                        if account.storage_root != EMPTY_ROOT_HASH {
+                            hashes.push(account.storage_root);
+                        }
+                    }
+                }
+                Node::Extension(extension_node) => collect(&extension_node.node, hashes),
+                Node::Branch(branch_node) => {
+                    for child in &branch_node.children {
+                        collect(child, hashes);
+                    }
+                }
+                Node::Hash(hash_node) => hashes.push(**hash_node),
+            }
+        }
+
+        let mut hashes = vec![];
+        // Stored nodes are always valid, undecodable node just doesn't reference anything
+        if let Ok(node) = Node::decode(&mut &encoded_node[..]) {
+            collect(&node, &mut hashes);
+        }
+        hashes
+    }
+
+    /// Writes the trie with given root to the batch and returns the root hash.
+    ///
+    /// The root node is always written, even if its encoding is shorter than 32 bytes, so that the
+    /// trie can be loaded from the Db using its root hash.
+    pub(crate) fn write_root(root: &Node, batch: &mut WriteBatch) -> B256 {
+        #[cfg(feature = "rayon")]
+        let encoded = root.par_encode(batch);
+        #[cfg(not(feature = "rayon"))]
+        let encoded = root.encode(batch);
+        match root {
+            Node::Nil => EMPTY_ROOT_HASH,
+            Node::Hash(hash) => **hash,
+            _ => {
+                let hash = keccak256(&encoded);
+                batch.write(hash, encoded);
+                hash
+            }
         }
     }
 
@@ -29,9 +193,29 @@ impl Mpt {
         Ok(())
     }
 
+    pub fn remove_raw(&mut self, path: &[Nibble]) -> Result<()> {
+        self.root.remove(path, &*self.db)?;
+        Ok(())
+    }
+
     pub fn get_raw(&self, path: &[Nibble]) -> Result<Option<Vec<u8>>> {
+        Self::get_from(&self.root, path, &*self.db)
+    }
+
+    /// Returns all changes between the tries with the given roots, which must both be stored in
+    /// the Db. See [diff].
+    pub fn diff(&self, old_root: B256, new_root: B256) -> Result<Vec<(Nibbles, ValueDiff)>> {
+        diff(old_root, new_root, &*self.db)
+    }
+
+    /// Returns iterator over all `(path, value)` pairs, in the ascending order of paths.
+    pub fn iter(&self) -> MptIterator<'_> {
+        MptIterator::new(&self.root, &*self.db)
+    }
+
+    fn get_from(root: &Node, path: &[Nibble], db: &Db) -> Result<Option<Vec<u8>>> {
         let mut node: Node;
-        let mut node_traversal_info = self.root.next_node(path);
+        let mut node_traversal_info = root.next_node(path);
         loop {
             match node_traversal_info {
                 NodeTraversalInfo::Empty => return Ok(None),
@@ -40,7 +224,7 @@ impl Mpt {
                     hash,
                     remaining_path,
                 } => {
-                    let Some(encoded_node) = self.db.read(&hash)? else {
+                    let Some(encoded_node) = db.read(&hash)? else {
                         bail!("Node missing from Db: {hash:?}")
                     };
                     node = Node::decode(&mut encoded_node.as_slice())?;
@@ -50,6 +234,149 @@ impl Mpt {
         }
     }
 
+    /// Returns RLP encoded nodes along the path, starting with the root node.
+    ///
+    /// Nodes that are embedded into their parent (because their encoding is shorter than 32 bytes)
+    /// are not returned separately. The trie must be committed with [Mpt::get_hash], proofs of
+    /// uncommitted changes are not supported.
+    pub fn get_proof(&self, path: &[Nibble]) -> Result<Vec<Vec<u8>>> {
+        self.get_proof_from(self.committed_root()?, path)
+    }
+
+    // Returns the proof from the trie (or the storage trie) with the given root
+    fn get_proof_from(&self, root: B256, path: &[Nibble]) -> Result<Vec<Vec<u8>>> {
+        let root_encoded = match root {
+            EMPTY_ROOT_HASH => return Ok(vec![]),
+            root => self.read_encoded_node(&root)?,
+        };
+
+        let mut node = Node::decode(&mut root_encoded.as_slice())?;
+        let mut proof = vec![root_encoded];
+        let mut remaining_path = path;
+        loop {
+            match node.next_node(remaining_path) {
+                NodeTraversalInfo::Empty | NodeTraversalInfo::Value(_) => return Ok(proof),
+                NodeTraversalInfo::NextNode {
+                    hash,
+                    remaining_path: next_remaining_path,
+                } => {
+                    let encoded_node = self.read_encoded_node(&hash)?;
+                    node = Node::decode(&mut encoded_node.as_slice())?;
+                    proof.push(encoded_node);
+                    remaining_path = next_remaining_path;
+                }
+            }
+        }
+    }
+
+    /// Returns the entries starting from `origin`, together with the proofs of the range edges.
+    ///
+    /// Iteration stops after the first key that is not smaller than `limit`, or after
+    /// `max_results` entries. All keys in the trie should be 32 bytes long.
+    pub fn get_range_proof(
+        &self,
+        origin: B256,
+        limit: B256,
+        max_results: usize,
+    ) -> Result<RangeProof> {
+        self.committed_root()?;
+
+        let mut keys = vec![];
+        let mut values = vec![];
+        let mut iterator = self.iter();
+        iterator.seek(&Nibbles::from_packed(origin));
+        for entry in iterator {
+            if keys.len() >= max_results {
+                break;
+            }
+            let (path, value) = entry?;
+            let Some(key) = path
+                .to_packed()
+                .and_then(|packed| B256::try_from(packed.as_slice()).ok())
+            else {
+                bail!("Key is not 32 bytes long: {path:?}")
+            };
+            keys.push(key);
+            values.push(value);
+            if key >= limit {
+                break;
+            }
+        }
+
+        let mut proof = self.get_proof(&Nibbles::from_packed(origin))?;
+        if let Some(last) = keys.last() {
+            for encoded_node in self.get_proof(&Nibbles::from_packed(last))? {
+                if !proof.contains(&encoded_node) {
+                    proof.push(encoded_node);
+                }
+            }
+        }
+
+        Ok(RangeProof {
+            keys,
+            values,
+            proof: proof.into_iter().map(Bytes::from).collect(),
+        })
+    }
+
+    /// Returns the proof of the account, together with the proofs of the given storage slots.
+    pub fn get_account_proof(
+        &self,
+        address: Address,
+        storage_keys: &[B256],
+    ) -> Result<AccountProof> {
+        let account = self.get_account(&address)?.unwrap_or_default();
+        let proof = self.get_proof(&Nibbles::from_packed(keccak256(address)))?;
+        let storage_proof = storage_keys
+            .iter()
+            .map(|key| self.get_storage_proof_from(account.storage_root, *key))
+            .collect::<Result<_>>()?;
+        Ok(AccountProof {
+            address,
+            account_proof: proof.into_iter().map(Bytes::from).collect(),
+            balance: account.balance,
+            code_hash: account.code_hash,
+            nonce: U64::from(account.nonce),
+            storage_hash: account.storage_root,
+            storage_proof,
+        })
+    }
+
+    /// Returns the proof of the storage slot, assuming that this is the storage trie.
+    pub fn get_storage_proof(&self, key: B256) -> Result<StorageProof> {
+        self.get_storage_proof_from(self.committed_root()?, key)
+    }
+
+    fn get_storage_proof_from(&self, storage_root: B256, key: B256) -> Result<StorageProof> {
+        let path = Nibbles::from_packed(keccak256(key));
+        let value = Self::get_from(&Self::root_node(storage_root), &path, &*self.db)?
+            .map(|encoded| U256::decode(&mut encoded.as_slice()))
+            .transpose()?
+            .unwrap_or_default();
+        let proof = self.get_proof_from(storage_root, &path)?;
+        Ok(StorageProof {
+            key,
+            value,
+            proof: proof.into_iter().map(Bytes::from).collect(),
+        })
+    }
+
+    // Returns the root of the trie, if there are no uncommitted changes
+    fn committed_root(&self) -> Result<B256> {
+        match &self.root {
+            Node::Nil if self.storage_tries.is_empty() => Ok(EMPTY_ROOT_HASH),
+            Node::Hash(hash) if self.storage_tries.is_empty() => Ok(**hash),
+            _ => bail!("Trie has uncommitted changes"),
+        }
+    }
+
+    fn read_encoded_node(&self, hash: &B256) -> Result<Vec<u8>> {
+        match self.db.read(hash)? {
+            Some(encoded_node) => Ok(encoded_node),
+            None => bail!("Node missing from Db: {hash:?}"),
+        }
+    }
+
     pub fn set_account(&mut self, address: Address, account: &AccountState) -> Result<()> {
         self.set_raw(
             &Nibbles::from_packed(keccak256(address)),
@@ -57,27 +384,110 @@ impl Mpt {
         )
     }
 
-    pub fn get_account(&mut self, address: &Address) -> Result<Option<AccountState>> {
+    pub fn remove_account(&mut self, address: &Address) -> Result<()> {
+        self.storage_tries.remove(address);
+        self.remove_raw(&Nibbles::from_packed(keccak256(address)))
+    }
+
+    pub fn get_account(&self, address: &Address) -> Result<Option<AccountState>> {
         Ok(self
             .get_raw(&Nibbles::from_packed(keccak256(address)))?
             .map(|encoded| AccountState::decode(&mut encoded.as_slice()))
             .transpose()?)
     }
+
+    /// Sets the value of the storage slot. Setting the zero value removes the slot.
+    ///
+    /// The `storage_root` of the account is updated when the trie is committed.
+    pub fn set_storage(&mut self, address: Address, slot: B256, value: U256) -> Result<()> {
+        let storage_trie = match self.storage_tries.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let account = Self::get_from(
+                    &self.root,
+                    &Nibbles::from_packed(keccak256(address)),
+                    &*self.db,
+                )?
+                .map(|encoded| AccountState::decode(&mut encoded.as_slice()))
+                .transpose()?;
+                entry.insert(Self::storage_trie_root(account))
+            }
+        };
+
+        let path = Nibbles::from_packed(keccak256(slot));
+        if value.is_zero() {
+            storage_trie.remove(&path, &*self.db)?;
+        } else {
+            storage_trie.update(&path, alloy_rlp::encode(value), &*self.db)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the storage slot, or zero if it isn't set.
+    pub fn get_storage(&mut self, address: &Address, slot: B256) -> Result<U256> {
+        let path = Nibbles::from_packed(keccak256(slot));
+        let encoded = match self.storage_tries.get(address) {
+            Some(storage_trie) => Self::get_from(storage_trie, &path, &*self.db)?,
+            None => {
+                let storage_trie = Self::storage_trie_root(self.get_account(address)?);
+                Self::get_from(&storage_trie, &path, &*self.db)?
+            }
+        };
+        Ok(encoded
+            .map(|encoded| U256::decode(&mut encoded.as_slice()))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Sets the contract code of the account and updates its `code_hash`.
+    ///
+    /// The code is stored in the Db (keyed by its hash) when the trie is committed.
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) -> Result<()> {
+        let mut account = self.get_account(&address)?.unwrap_or_default();
+        account.code_hash = keccak256(&code);
+        self.set_account(address, &account)?;
+        if account.code_hash != EMPTY_CODE_HASH {
+            self.pending_code.insert(account.code_hash, code);
+        }
+        Ok(())
+    }
+
+    /// Returns the contract code of the account, or None if account doesn't exist.
+    pub fn get_code(&mut self, address: &Address) -> Result<Option<Vec<u8>>> {
+        let Some(account) = self.get_account(address)? else {
+            return Ok(None);
+        };
+        if account.code_hash == EMPTY_CODE_HASH {
+            return Ok(Some(vec![]));
+        }
+        if let Some(code) = self.pending_code.get(&account.code_hash) {
+            return Ok(Some(code.clone()));
+        }
+        match self.db.read(&account.code_hash)? {
+            Some(code) => Ok(Some(code)),
+            None => bail!("Code missing from Db: {:?}", account.code_hash),
+        }
+    }
+
+    fn storage_trie_root(account: Option<AccountState>) -> Node {
+        Self::root_node(account.map_or(EMPTY_ROOT_HASH, |account| account.storage_root))
+    }
 }
 
 impl Default for Mpt {
     fn default() -> Self {
-        Self {
-            root: Node::Nil,
-            db: Box::new(MemoryDb::new()),
-        }
+        Self::new(Box::new(MemoryDb::new()))
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use std::{str::FromStr, sync::Arc};
+    use std::{cell::Cell, rc::Rc, str::FromStr, sync::Arc};
+
+    use db::errors::DbError;
+
+    use crate::proof::{verify_account_proof, verify_proof, verify_storage_proof};
 
     use super::*;
 
@@ -152,25 +562,653 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn remove_all() -> Result<()> {
+        let mut tree = Mpt::default();
+        let empty_root = tree.get_hash()?;
+
+        tree.set_raw(&Nibbles::from_packed(b"first"), b"value".to_vec())?;
+        tree.set_raw(&Nibbles::from_packed(b"second"), b"value".to_vec())?;
+        tree.get_hash()?;
+
+        tree.remove_raw(&Nibbles::from_packed(b"first"))?;
+        tree.remove_raw(&Nibbles::from_packed(b"second"))?;
+
+        assert_eq!(tree.get_hash()?, empty_root);
+        assert!(tree.get_raw(&Nibbles::from_packed(b"first"))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn remove_missing() -> Result<()> {
+        let mut tree = Mpt::default();
+
+        tree.set_raw(&Nibbles::from_packed(b"first"), b"value".to_vec())?;
+        tree.set_raw(&Nibbles::from_packed(b"second"), b"value".to_vec())?;
+        let root = tree.get_hash()?;
+
+        tree.remove_raw(&Nibbles::from_packed(b"third"))?;
+        tree.remove_raw(&Nibbles::from_packed(b"firs"))?;
+
+        assert_eq!(tree.get_hash()?, root);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_account() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address1 = Address::repeat_byte(1);
+        let address2 = Address::repeat_byte(2);
+
+        tree.set_account(address1, &AccountState::default())?;
+        let root = tree.get_hash()?;
+
+        tree.set_account(address2, &AccountState::default())?;
+        tree.get_hash()?;
+        tree.remove_account(&address2)?;
+
+        assert_eq!(tree.get_hash()?, root);
+        assert!(tree.get_account(&address2)?.is_none());
+        assert!(tree.get_account(&address1)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn compute_hashes_after_remove() {
+        let data = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+        ];
+        for removed in [
+            vec![b"do".to_vec()],
+            vec![b"dog".to_vec()],
+            vec![b"doge".to_vec()],
+            vec![b"horse".to_vec()],
+            vec![b"dog".to_vec(), b"doge".to_vec()],
+            vec![b"do".to_vec(), b"horse".to_vec()],
+            vec![b"do".to_vec(), b"dog".to_vec(), b"doge".to_vec()],
+        ] {
+            expect_hash_after_remove(data.clone(), removed).unwrap();
+        }
+    }
+
+    #[test]
+    fn compute_hashes_after_remove_hashed_nodes() {
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+            .map(|i| (keccak256([i]).to_vec(), vec![i; 40]))
+            .collect();
+        let removed = data
+            .iter()
+            .step_by(3)
+            .map(|(path, _)| path.clone())
+            .collect();
+        expect_hash_after_remove(data, removed).unwrap();
+    }
+
+    #[test]
+    fn proof() -> Result<()> {
+        let data = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"doge".to_vec(), b"coin".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+        ];
+        let mut tree = Mpt::default();
+        for (path, value) in &data {
+            tree.set_raw(&Nibbles::from_packed(path), value.clone())?;
+        }
+        let root = tree.get_hash()?;
+
+        for (path, value) in data.iter().cloned() {
+            let path = Nibbles::from_packed(path);
+            let proof = tree.get_proof(&path)?;
+            assert_eq!(verify_proof(root, &path, &proof)?, Some(value));
+        }
+        for path in [b"d".to_vec(), b"dogs".to_vec(), b"cat".to_vec(), vec![]] {
+            let path = Nibbles::from_packed(path);
+            let proof = tree.get_proof(&path)?;
+            assert_eq!(verify_proof(root, &path, &proof)?, None);
+        }
+
+        // Proofs are only created for committed tries
+        tree.set_raw(&Nibbles::from_packed(b"cat"), b"kitten".to_vec())?;
+        assert!(tree.get_proof(&Nibbles::from_packed(b"dog")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn proof_empty() -> Result<()> {
+        let mut tree = Mpt::default();
+        let root = tree.get_hash()?;
+        let path = Nibbles::from_packed(b"dog");
+
+        let proof = tree.get_proof(&path)?;
+        assert!(proof.is_empty());
+        assert_eq!(verify_proof(root, &path, &proof)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn proof_invalid() -> Result<()> {
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+            .map(|i| (keccak256([i]).to_vec(), vec![i; 40]))
+            .collect();
+        let mut tree = Mpt::default();
+        for (path, value) in &data {
+            tree.set_raw(&Nibbles::from_packed(path), value.clone())?;
+        }
+        let root = tree.get_hash()?;
+
+        let path = Nibbles::from_packed(&data[0].0);
+        let mut proof = tree.get_proof(&path)?;
+        proof.pop();
+        assert!(verify_proof(root, &path, &proof).is_err());
+        assert!(verify_proof(B256::ZERO, &path, &tree.get_proof(&path)?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn account_proof() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        let account = AccountState::new_eoa(U256::from(1000));
+        tree.set_account(address, &account)?;
+        tree.set_account(Address::repeat_byte(2), &AccountState::default())?;
+        let root = tree.get_hash()?;
+
+        let account_proof = tree.get_account_proof(address, &[])?;
+        assert_eq!(account_proof.balance, account.balance);
+        verify_account_proof(root, &account_proof)?;
+
+        let missing_account_proof = tree.get_account_proof(Address::repeat_byte(3), &[])?;
+        verify_account_proof(root, &missing_account_proof)?;
+
+        let mut invalid_account_proof = account_proof;
+        invalid_account_proof.balance += U256::from(1);
+        assert!(verify_account_proof(root, &invalid_account_proof).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn account_proof_with_storage() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        tree.set_account(address, &AccountState::new_eoa(U256::from(1000)))?;
+        for i in 1u8..50 {
+            tree.set_storage(address, B256::with_last_byte(i), U256::from(i))?;
+        }
+        let root = tree.get_hash()?;
+
+        let keys = [B256::with_last_byte(1), B256::with_last_byte(0xff)];
+        let account_proof = tree.get_account_proof(address, &keys)?;
+        verify_account_proof(root, &account_proof)?;
+        assert_eq!(account_proof.storage_proof.len(), keys.len());
+        assert_eq!(account_proof.storage_proof[0].key, keys[0]);
+        assert_eq!(account_proof.storage_proof[0].value, U256::from(1));
+        assert_eq!(account_proof.storage_proof[1].value, U256::ZERO);
+        for storage_proof in &account_proof.storage_proof {
+            verify_storage_proof(account_proof.storage_hash, storage_proof)?;
+        }
+
+        // Missing account has empty storage
+        let missing_account_proof = tree.get_account_proof(Address::repeat_byte(2), &keys)?;
+        for storage_proof in &missing_account_proof.storage_proof {
+            assert_eq!(storage_proof.value, U256::ZERO);
+            verify_storage_proof(missing_account_proof.storage_hash, storage_proof)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn storage_proof() -> Result<()> {
+        let mut storage = Mpt::default();
+        let key = B256::with_last_byte(1);
+        let value = U256::from(0x1234);
+        storage.set_raw(
+            &Nibbles::from_packed(keccak256(key)),
+            alloy_rlp::encode(value),
+        )?;
+        let storage_root = storage.get_hash()?;
+
+        let storage_proof = storage.get_storage_proof(key)?;
+        assert_eq!(storage_proof.value, value);
+        verify_storage_proof(storage_root, &storage_proof)?;
+
+        let missing_storage_proof = storage.get_storage_proof(B256::with_last_byte(2))?;
+        assert_eq!(missing_storage_proof.value, U256::ZERO);
+        verify_storage_proof(storage_root, &missing_storage_proof)?;
+        Ok(())
+    }
+
+    #[test]
+    fn storage() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        let slots = (0u8..20)
+            .map(|i| (B256::with_last_byte(i), U256::from(i) * U256::from(1000)))
+            .collect::<Vec<_>>();
+
+        tree.set_account(address, &AccountState::new_eoa(U256::from(1000)))?;
+        let mut expected_storage = Mpt::default();
+        for (slot, value) in &slots {
+            tree.set_storage(address, *slot, *value)?;
+            if !value.is_zero() {
+                expected_storage.set_raw(
+                    &Nibbles::from_packed(keccak256(slot)),
+                    alloy_rlp::encode(value),
+                )?;
+            }
+        }
+        for (slot, value) in &slots {
+            assert_eq!(tree.get_storage(&address, *slot)?, *value);
+        }
+
+        tree.get_hash()?;
+        let account = tree.get_account(&address)?.unwrap();
+        assert_eq!(account.storage_root, expected_storage.get_hash()?);
+        assert_eq!(account.balance, U256::from(1000));
+        for (slot, value) in &slots {
+            assert_eq!(tree.get_storage(&address, *slot)?, *value);
+        }
+
+        // Update and remove after commit
+        tree.set_storage(address, slots[1].0, U256::from(1))?;
+        for (slot, _) in &slots[2..] {
+            tree.set_storage(address, *slot, U256::ZERO)?;
+        }
+        tree.get_hash()?;
+        assert_eq!(tree.get_storage(&address, slots[1].0)?, U256::from(1));
+        assert_eq!(tree.get_storage(&address, slots[2].0)?, U256::ZERO);
+
+        for (slot, _) in &slots {
+            tree.set_storage(address, *slot, U256::ZERO)?;
+        }
+        tree.get_hash()?;
+        assert_eq!(
+            tree.get_account(&address)?.unwrap().storage_root,
+            EMPTY_ROOT_HASH
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn storage_missing_account() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        let slot = B256::with_last_byte(1);
+        assert_eq!(tree.get_storage(&address, slot)?, U256::ZERO);
+
+        tree.set_storage(address, slot, U256::from(1))?;
+        tree.get_hash()?;
+
+        let account = tree.get_account(&address)?.unwrap();
+        assert_ne!(account.storage_root, EMPTY_ROOT_HASH);
+        assert_eq!(tree.get_storage(&address, slot)?, U256::from(1));
+        assert_eq!(
+            tree.get_storage(&Address::repeat_byte(2), slot)?,
+            U256::ZERO
+        );
+
+        // Empty storage doesn't create the account
+        let root = tree.get_hash()?;
+        tree.set_storage(Address::repeat_byte(2), slot, U256::ZERO)?;
+        tree.set_storage(Address::repeat_byte(3), slot, U256::from(1))?;
+        tree.set_storage(Address::repeat_byte(3), slot, U256::ZERO)?;
+        assert_eq!(tree.get_hash()?, root);
+        assert!(tree.get_account(&Address::repeat_byte(2))?.is_none());
+        assert!(tree.get_account(&Address::repeat_byte(3))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn code() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        let code = vec![0x60, 0x00, 0x60, 0x00, 0xf3];
+        assert!(tree.get_code(&address)?.is_none());
+
+        tree.set_account(address, &AccountState::new_eoa(U256::from(1000)))?;
+        assert_eq!(tree.get_code(&address)?, Some(vec![]));
+
+        tree.set_code(address, code.clone())?;
+        assert_eq!(tree.get_code(&address)?, Some(code.clone()));
+
+        tree.get_hash()?;
+        let account = tree.get_account(&address)?.unwrap();
+        assert_eq!(account.code_hash, keccak256(&code));
+        assert_eq!(account.balance, U256::from(1000));
+        assert_eq!(tree.get_code(&address)?, Some(code));
+
+        tree.set_code(address, vec![])?;
+        tree.get_hash()?;
+        assert_eq!(
+            tree.get_account(&address)?.unwrap().code_hash,
+            EMPTY_CODE_HASH
+        );
+        assert_eq!(tree.get_code(&address)?, Some(vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn commit_and_checkout() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        let empty_root = tree.commit(0)?;
+        for block in 1u8..=5 {
+            tree.set_account(address, &AccountState::new_eoa(U256::from(block)))?;
+            tree.set_storage(address, B256::with_last_byte(block), U256::from(block))?;
+            tree.commit(block.into())?;
+        }
+        let root_3 = tree.root_at(3).unwrap();
+
+        // Revert to block 2 and replay different block 3
+        tree.set_storage(address, B256::with_last_byte(10), U256::from(10))?;
+        tree.checkout(tree.root_at(2).unwrap())?;
+        assert_eq!(tree.get_account(&address)?.unwrap().balance, U256::from(2));
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(2))?,
+            U256::from(2)
+        );
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(3))?,
+            U256::ZERO
+        );
+        // Uncommitted changes were discarded
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(10))?,
+            U256::ZERO
+        );
+
+        let root_5 = tree.root_at(5).unwrap();
+        tree.set_storage(address, B256::with_last_byte(3), U256::from(33))?;
+        let forked_root_3 = tree.commit(3)?;
+        assert_ne!(forked_root_3, root_3);
+        assert_eq!(tree.root_at(3), Some(forked_root_3));
+        // Later blocks of the original chain were dropped
+        assert_eq!(tree.root_at(4), None);
+        assert_eq!(tree.root_at(5), None);
+
+        // Original chain is still stored
+        tree.checkout(root_3)?;
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(3))?,
+            U256::from(3)
+        );
+        tree.checkout(root_5)?;
+        assert_eq!(tree.get_account(&address)?.unwrap().balance, U256::from(5));
+        tree.checkout(empty_root)?;
+        assert!(tree.get_account(&address)?.is_none());
+
+        assert!(tree.checkout(B256::repeat_byte(0xab)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pruned_history() -> Result<()> {
+        let mut tree = Mpt::default();
+        tree.enable_pruning(2);
+        let address = Address::repeat_byte(1);
+        for block in 1u8..=4 {
+            tree.set_account(address, &AccountState::new_eoa(U256::from(block)))?;
+            tree.commit(block.into())?;
+        }
+
+        assert_eq!(tree.root_at(1), None);
+        assert_eq!(tree.root_at(2), None);
+        tree.checkout(tree.root_at(3).unwrap())?;
+        assert_eq!(tree.get_account(&address)?.unwrap().balance, U256::from(3));
+        tree.checkout(tree.root_at(4).unwrap())?;
+        assert_eq!(tree.get_account(&address)?.unwrap().balance, U256::from(4));
+        Ok(())
+    }
+
+    #[test]
+    fn new_with_root() -> Result<()> {
+        let mut tree = Mpt::default();
+        let address = Address::repeat_byte(1);
+        tree.set_account(address, &AccountState::new_eoa(U256::from(1000)))?;
+        tree.set_storage(address, B256::with_last_byte(1), U256::from(1))?;
+        let root = tree.get_hash()?;
+
+        let Mpt { db, .. } = tree;
+        let mut tree = Mpt::new_with_root(root, db);
+        assert_eq!(tree.get_hash()?, root);
+        assert_eq!(
+            tree.get_account(&address)?.unwrap().balance,
+            U256::from(1000)
+        );
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(1))?,
+            U256::from(1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write() -> Result<()> {
+        // Db that fails to write batches while `fail` is set
+        struct FailingDb {
+            db: MemoryDb<B256, Vec<u8>>,
+            fail: Rc<Cell<bool>>,
+        }
+
+        impl db::Db<B256, Vec<u8>> for FailingDb {
+            fn write(&mut self, key: B256, value: Vec<u8>) -> Result<(), DbError> {
+                self.db.write(key, value)
+            }
+
+            fn read(&self, key: &B256) -> Result<Option<Vec<u8>>, DbError> {
+                self.db.read(key)
+            }
+
+            fn write_batch(&mut self, batch: WriteBatch) -> Result<(), DbError> {
+                if self.fail.get() {
+                    return Err(DbError::Error);
+                }
+                self.db.write_batch(batch)
+            }
+        }
+
+        let fail = Rc::new(Cell::new(false));
+        let mut tree = Mpt::new(Box::new(FailingDb {
+            db: MemoryDb::new(),
+            fail: fail.clone(),
+        }));
+        let mut expected_tree = Mpt::default();
+        for tree in [&mut tree, &mut expected_tree] {
+            for i in 0u8..50 {
+                tree.set_account(
+                    Address::repeat_byte(i),
+                    &AccountState::new_eoa(U256::from(i)),
+                )?;
+            }
+            tree.get_hash()?;
+            let address = Address::repeat_byte(1);
+            tree.set_account(address, &AccountState::new_eoa(U256::from(1000)))?;
+            tree.set_storage(address, B256::with_last_byte(1), U256::from(1))?;
+            tree.set_code(address, vec![0x60, 0x00, 0x60, 0x00, 0xf3])?;
+        }
+
+        fail.set(true);
+        assert!(tree.get_hash().is_err());
+        // Modified nodes were kept, so nothing is lost
+        fail.set(false);
+        assert_eq!(tree.get_hash()?, expected_tree.get_hash()?);
+        let address = Address::repeat_byte(1);
+        assert_eq!(
+            tree.get_account(&address)?.map(|account| account.balance),
+            Some(U256::from(1000))
+        );
+        assert_eq!(
+            tree.get_storage(&address, B256::with_last_byte(1))?,
+            U256::from(1)
+        );
+        assert_eq!(
+            tree.get_code(&address)?,
+            Some(vec![0x60, 0x00, 0x60, 0x00, 0xf3])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pruning() -> Result<()> {
+        // Loads all nodes of the account trie and the storage tries with the given root
+        fn load_all(tree: &Mpt, root: B256) -> Result<()> {
+            for (_, value_diff) in diff(EMPTY_ROOT_HASH, root, &*tree.db)? {
+                let ValueDiff::Added(encoded) = value_diff else {
+                    bail!("Unexpected diff from empty trie: {value_diff:?}")
+                };
+                let account = AccountState::decode(&mut encoded.as_slice())?;
+                diff(EMPTY_ROOT_HASH, account.storage_root, &*tree.db)?;
+            }
+            Ok(())
+        }
+
+        let mut tree = Mpt::default();
+        tree.enable_pruning(2);
+
+        let mut roots = vec![];
+        let mut storage_roots = vec![];
+        for round in 0u8..10 {
+            for i in 0u8..50 {
+                let address = Address::repeat_byte(i);
+                let mut account = tree.get_account(&address)?.unwrap_or_default();
+                account.balance = U256::from(round) * U256::from(1000) + U256::from(i);
+                tree.set_account(address, &account)?;
+                for slot in 0..i % 5 {
+                    tree.set_storage(address, B256::with_last_byte(slot), U256::from(round + 1))?;
+                }
+            }
+            if round == 5 {
+                tree.remove_account(&Address::repeat_byte(4))?;
+            }
+            roots.push(tree.get_hash()?);
+            storage_roots.push(
+                tree.get_account(&Address::repeat_byte(4))?
+                    .map(|account| account.storage_root),
+            );
+
+            // All nodes of the retained roots are still stored
+            for root in roots.iter().rev().take(2) {
+                load_all(&tree, *root)?;
+            }
+        }
+
+        // Older roots and their storage tries are deleted
+        for root in &roots[..roots.len() - 2] {
+            assert!(tree.db.read(root)?.is_none());
+        }
+        for storage_root in storage_roots[..5].iter().flatten() {
+            assert!(tree.db.read(storage_root)?.is_none());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_write() -> Result<()> {
+        let mut sequential = Mpt::default();
+        let mut parallel = Mpt::default();
+        for round in 0u32..3 {
+            // Second round updates the already committed trie, and the third round only the
+            // subtrees of two children of the root
+            for i in 0u32..5000 {
+                let path = Nibbles::from_packed(keccak256((round * 2500 + i).to_be_bytes()));
+                if round == 2 && *path[0] >= 2 {
+                    continue;
+                }
+                let value = vec![round as u8; 1 + i as usize % 40];
+                sequential.set_raw(&path, value.clone())?;
+                parallel.set_raw(&path, value)?;
+            }
+
+            let mut sequential_batch = WriteBatch::new();
+            let mut parallel_batch = WriteBatch::new();
+            let encoded = parallel.root.par_encode(&mut parallel_batch);
+            assert_eq!(encoded, sequential.root.encode(&mut sequential_batch));
+            // Writes are added to the batch in different order
+            let parallel_writes = parallel_batch.writes().collect::<HashMap<_, _>>();
+            let sequential_writes = sequential_batch.writes().collect::<HashMap<_, _>>();
+            assert_eq!(parallel_writes, sequential_writes);
+
+            let root = keccak256(&encoded);
+            for (tree, mut batch) in [
+                (&mut parallel, parallel_batch),
+                (&mut sequential, sequential_batch),
+            ] {
+                batch.write(root, encoded.clone());
+                tree.db.write_batch(batch)?;
+                tree.root = Node::Hash(root.into());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn proofs_match_cita_trie() -> Result<()> {
+        use cita_trie::{MemoryDB, PatriciaTrie, Trie};
+        use hasher::HasherKeccak;
+
+        let data: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64)
+            .map(|i| (keccak256([i]).to_vec(), vec![i; 1 + i as usize]))
+            .collect();
+
+        let mut trie =
+            PatriciaTrie::new(Arc::new(MemoryDB::new(true)), Arc::new(HasherKeccak::new()));
+        let mut tree = Mpt::default();
+        for (path, value) in &data {
+            trie.insert(path.clone(), value.clone()).unwrap();
+            tree.set_raw(&Nibbles::from_packed(path), value.clone())?;
+        }
+        trie.root().unwrap();
+        tree.get_hash()?;
+
+        for path in data.iter().map(|(path, _)| path).chain([&vec![0x12, 0x34]]) {
+            assert_eq!(
+                tree.get_proof(&Nibbles::from_packed(path))?,
+                trie.get_proof(path).unwrap()
+            );
+        }
+        Ok(())
+    }
+
     fn expect_hash(data: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        expect_hash_after_remove(data, vec![])
+    }
+
+    fn expect_hash_after_remove(
+        data: Vec<(Vec<u8>, Vec<u8>)>,
+        removed: Vec<Vec<u8>>,
+    ) -> Result<()> {
         assert_eq!(
-            compute_hash_cita_trie(data.clone())?,
-            compute_hash_ours(data)?
+            compute_hash_cita_trie(data.clone(), removed.clone())?,
+            compute_hash_ours(data, removed)?
         );
         Ok(())
     }
 
-    fn compute_hash_ours(data: Vec<(Vec<u8>, Vec<u8>)>) -> Result<B256> {
+    fn compute_hash_ours(data: Vec<(Vec<u8>, Vec<u8>)>, removed: Vec<Vec<u8>>) -> Result<B256> {
         let mut tree = Mpt::default();
 
         for (path, val) in data {
             tree.set_raw(&Nibbles::from_packed(path), val)?;
         }
+        // Commit, so that removal has to load nodes from the Db
+        tree.get_hash()?;
+
+        for path in removed {
+            tree.remove_raw(&Nibbles::from_packed(path))?;
+        }
 
         tree.get_hash()
     }
 
-    fn compute_hash_cita_trie(data: Vec<(Vec<u8>, Vec<u8>)>) -> Result<B256> {
+    fn compute_hash_cita_trie(
+        data: Vec<(Vec<u8>, Vec<u8>)>,
+        removed: Vec<Vec<u8>>,
+    ) -> Result<B256> {
         use cita_trie::{MemoryDB, PatriciaTrie, Trie};
         use hasher::HasherKeccak;
 
@@ -183,6 +1221,10 @@ mod test {
             trie.insert(path.to_vec(), value.to_vec()).unwrap();
         }
 
+        for path in removed {
+            trie.remove(&path).unwrap();
+        }
+
         trie.root()
             .map(|value| B256::from_slice(&value))
             .map_err(anyhow::Error::new)
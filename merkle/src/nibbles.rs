@@ -3,7 +3,7 @@ use std::{cmp, fmt::Write, ops::Deref};
 use anyhow::{bail, Result};
 use derive_more::{Deref, Index, LowerHex, UpperHex};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Deref, LowerHex, UpperHex)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deref, LowerHex, UpperHex)]
 pub struct Nibble(u8);
 
 impl Nibble {
@@ -48,6 +48,15 @@ impl Nibbles {
         Self(nibbles.as_ref().to_vec())
     }
 
+    /// Packs pairs of nibbles into bytes. Returns `None` if the number of nibbles is odd.
+    pub fn to_packed(&self) -> Option<Vec<u8>> {
+        let pairs = self.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return None;
+        }
+        Some(pairs.map(|pair| Nibble::join(pair[0], pair[1])).collect())
+    }
+
     // Public util functions
 
     pub fn common_prefix(&self, other: &[Nibble]) -> usize {
@@ -1,6 +1,14 @@
+use std::{iter, mem};
+
+use anyhow::{bail, Result};
 use derive_more::{Index, IndexMut};
 
-use super::Node;
+use crate::{
+    nibbles::{Nibble, Nibbles},
+    Db,
+};
+
+use super::{ExtensionNode, LeafNode, Node, UpdateNodeInfo};
 
 #[derive(Default, Index, IndexMut)]
 pub struct BranchNode {
@@ -23,4 +31,61 @@ impl BranchNode {
         branch_node[index] = child;
         branch_node
     }
+
+    /// Collapses the branch node if it doesn't have at least two non-empty items (children or
+    /// value), as required by the canonical form of the trie.
+    pub(crate) fn normalize(mut self: Box<Self>, db: &Db) -> Result<(Node, UpdateNodeInfo)> {
+        let mut non_empty_children = self
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| !matches!(child, Node::Nil))
+            .map(|(index, _)| index);
+
+        let index = match (
+            non_empty_children.next(),
+            non_empty_children.next(),
+            self.value.is_empty(),
+        ) {
+            (Some(index), None, true) => index,
+            (None, None, true) => return Ok((Node::Nil, UpdateNodeInfo::default())),
+            (None, None, false) => {
+                return Ok((
+                    Node::Leaf(LeafNode::new(Nibbles::from_slice([]), self.value)),
+                    UpdateNodeInfo::default(),
+                ))
+            }
+            _ => return Ok((Node::Branch(self), UpdateNodeInfo::default())),
+        };
+
+        let mut update_node_info = UpdateNodeInfo::default();
+        let mut child = mem::take(&mut self[index]);
+        if let Some(hash) = child.resolve(db)? {
+            update_node_info.updated_nodes.insert(hash);
+        }
+
+        let index = Nibble::try_from(index as u8)?;
+        let node = match child {
+            Node::Leaf(leaf_node) => Node::Leaf(LeafNode::new(
+                iter::once(index)
+                    .chain(leaf_node.prefix.iter().copied())
+                    .collect(),
+                leaf_node.value,
+            )),
+            Node::Extension(extension_node) => Node::Extension(
+                ExtensionNode::new(
+                    iter::once(index)
+                        .chain(extension_node.prefix.iter().copied())
+                        .collect(),
+                    extension_node.node,
+                )
+                .into(),
+            ),
+            Node::Branch(_) => {
+                Node::Extension(ExtensionNode::new(Nibbles::from_slice([index]), child).into())
+            }
+            Node::Nil | Node::Hash(_) => bail!("Unexpected child node while normalizing branch"),
+        };
+        Ok((node, update_node_info))
+    }
 }
@@ -15,6 +15,35 @@ impl ExtensionNode {
         Self { prefix, node }
     }
 
+    /// Merges the extension node with its child, if the child is not a branch node.
+    pub(crate) fn normalize(self) -> Node {
+        match self.node {
+            Node::Nil => Node::Nil,
+            Node::Leaf(leaf_node) => Node::Leaf(LeafNode::new(
+                self.prefix
+                    .iter()
+                    .chain(leaf_node.prefix.iter())
+                    .copied()
+                    .collect(),
+                leaf_node.value,
+            )),
+            Node::Extension(extension_node) => Node::Extension(
+                ExtensionNode::new(
+                    self.prefix
+                        .iter()
+                        .chain(extension_node.prefix.iter())
+                        .copied()
+                        .collect(),
+                    extension_node.node,
+                )
+                .into(),
+            ),
+            node @ (Node::Branch(_) | Node::Hash(_)) => {
+                Node::Extension(ExtensionNode::new(self.prefix, node).into())
+            }
+        }
+    }
+
     pub(crate) fn update(mut self, path: &[Nibble], value: Vec<u8>) -> Node {
         let common_path_prefix = self.prefix.common_prefix(path);
 
@@ -3,14 +3,21 @@ use std::{collections::BTreeSet, mem};
 use alloy_primitives::{keccak256, B256};
 use alloy_rlp::{Buf, BufMut, Decodable, Encodable, Header};
 use anyhow::{bail, Result};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::{
     nibbles::{Nibble, Nibbles},
-    Db,
+    Db, WriteBatch,
 };
 
 use super::{BranchNode, ExtensionNode, HashNode, LeafNode};
 
+/// The minimal number of modified children of a branch for which it's worth writing them in
+/// parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_WRITE_MIN_CHILDREN: usize = 4;
+
 #[derive(Default)]
 pub enum Node {
     #[default]
@@ -63,6 +70,7 @@ impl Node {
                 Some((first, remaining_path)) => {
                     branch_node[**first as usize].next_node(remaining_path)
                 }
+                None if branch_node.value.is_empty() => NodeTraversalInfo::Empty,
                 None => NodeTraversalInfo::Value(&branch_node.value),
             },
             Node::Hash(hash_node) => NodeTraversalInfo::NextNode {
@@ -116,25 +124,110 @@ impl Node {
             },
             Node::Hash(hash_node) => {
                 let hash = **hash_node;
-                let Some(encoded_node) = db.read(&hash)? else {
-                    bail!("Node with hash {hash:?} not found in db")
-                };
-                let node = Node::decode(&mut encoded_node.as_slice())?;
-                if matches!(node, Node::Hash(_)) {
-                    bail!("Decoded node is Hash node. hash: {hash:?}")
+                *self = Self::read_from_db(hash, db)?;
+                let mut updated_node_info = self.update(path, value, db)?;
+                updated_node_info.updated_nodes.insert(hash);
+                Ok(updated_node_info)
+            }
+        }
+    }
+
+    /// Removes the value at the given path (if present), keeping the trie in its canonical form.
+    pub fn remove(&mut self, path: &[Nibble], db: &Db) -> Result<UpdateNodeInfo> {
+        match self {
+            Node::Nil => Ok(UpdateNodeInfo::default()),
+            Node::Leaf(leaf_node) => {
+                if *leaf_node.prefix == path {
+                    *self = Node::Nil;
                 }
+                Ok(UpdateNodeInfo::default())
+            }
+            Node::Extension(extension_node) => {
+                if !path.starts_with(&extension_node.prefix) {
+                    return Ok(UpdateNodeInfo::default());
+                }
+                let updated_node_info = extension_node
+                    .node
+                    .remove(&path[extension_node.prefix.len()..], db)?;
+                // Replace extension_node with dummy
+                let extension_node = mem::replace(
+                    extension_node,
+                    ExtensionNode {
+                        prefix: Nibbles::from_slice([]),
+                        node: Node::Nil,
+                    }
+                    .into(),
+                );
+                *self = extension_node.normalize();
+                Ok(updated_node_info)
+            }
+            Node::Branch(branch_node) => {
+                let mut updated_node_info = match path.split_first() {
+                    Some((first, remaining_path)) => {
+                        branch_node[**first as usize].remove(remaining_path, db)?
+                    }
+                    None => {
+                        branch_node.value.clear();
+                        UpdateNodeInfo::default()
+                    }
+                };
+                // Replace branch_node with dummy
+                let branch_node = mem::take(branch_node);
+                let (node, normalize_node_info) = branch_node.normalize(db)?;
                 *self = node;
-                let mut updated_node_info = self.update(path, value, db)?;
+                updated_node_info
+                    .updated_nodes
+                    .extend(normalize_node_info.updated_nodes);
+                Ok(updated_node_info)
+            }
+            Node::Hash(hash_node) => {
+                let hash = **hash_node;
+                *self = Self::read_from_db(hash, db)?;
+                let mut updated_node_info = self.remove(path, db)?;
                 updated_node_info.updated_nodes.insert(hash);
                 Ok(updated_node_info)
             }
         }
     }
 
-    pub fn write(&mut self, db: &mut Db) -> Result<Vec<u8>> {
-        let encoded = match self {
-            Node::Nil => return Ok(vec![alloy_rlp::EMPTY_STRING_CODE]),
-            Node::Hash(hash_node) => return Ok(alloy_rlp::encode(**hash_node)),
+    /// Replaces the Hash node with the node loaded from the db. Returns the hash of the loaded
+    /// node, or None if node wasn't Hash node.
+    pub(crate) fn resolve(&mut self, db: &Db) -> Result<Option<B256>> {
+        let Node::Hash(hash_node) = self else {
+            return Ok(None);
+        };
+        let hash = **hash_node;
+        *self = Self::read_from_db(hash, db)?;
+        Ok(Some(hash))
+    }
+
+    fn read_from_db(hash: B256, db: &Db) -> Result<Node> {
+        let Some(encoded_node) = db.read(&hash)? else {
+            bail!("Node with hash {hash:?} not found in db")
+        };
+        let node = Node::decode(&mut encoded_node.as_slice())?;
+        if matches!(node, Node::Hash(_)) {
+            bail!("Decoded node is Hash node. hash: {hash:?}")
+        }
+        Ok(node)
+    }
+
+    /// Encodes the node. Nodes whose encoding is at least 32 bytes long are added to the batch and
+    /// replaced with the Hash node.
+    pub fn write(&mut self, batch: &mut WriteBatch) -> Vec<u8> {
+        let (encoded, hash) = self.write_reference(batch);
+        if let Some(hash) = hash {
+            *self = Node::Hash(hash.into());
+        }
+        encoded
+    }
+
+    /// Encodes the node without replacing it. Modified descendants whose encoding is at least 32
+    /// bytes long are added to the batch and referenced by their hash.
+    pub fn encode(&self, batch: &mut WriteBatch) -> Vec<u8> {
+        match self {
+            Node::Nil => vec![alloy_rlp::EMPTY_STRING_CODE],
+            Node::Hash(hash_node) => alloy_rlp::encode(**hash_node),
             Node::Leaf(leaf_node) => {
                 let mut payload = vec![];
                 leaf_node
@@ -153,50 +246,120 @@ impl Node {
                 buf.put_slice(&payload);
                 buf
             }
-            Node::Extension(extension_node) => {
-                let mut payload = vec![];
-                extension_node
-                    .prefix
-                    .to_compact(/*is_leaf=*/ false)
-                    .as_slice()
-                    .encode(&mut payload);
-                payload.put_slice(&extension_node.node.write(db)?);
+            Node::Extension(extension_node) => Self::encode_extension(
+                &extension_node.prefix,
+                &extension_node.node.write_reference(batch).0,
+            ),
+            Node::Branch(branch_node) => Self::encode_branch(
+                branch_node
+                    .children
+                    .iter()
+                    .map(|child| child.write_reference(batch).0),
+                &branch_node.value,
+            ),
+        }
+    }
 
-                let mut buf = vec![];
-                Header {
-                    list: true,
-                    payload_length: payload.len(),
-                }
-                .encode(&mut buf);
-                buf.put_slice(&payload);
-                buf
-            }
+    fn encode_extension(prefix: &Nibbles, child_reference: &[u8]) -> Vec<u8> {
+        let mut payload = vec![];
+        prefix
+            .to_compact(/*is_leaf=*/ false)
+            .as_slice()
+            .encode(&mut payload);
+        payload.put_slice(child_reference);
+
+        let mut buf = vec![];
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut buf);
+        buf.put_slice(&payload);
+        buf
+    }
+
+    fn encode_branch(child_references: impl IntoIterator<Item = Vec<u8>>, value: &[u8]) -> Vec<u8> {
+        let mut payload = vec![];
+        for child_reference in child_references {
+            payload.put_slice(&child_reference);
+        }
+        value.encode(&mut payload);
+
+        let mut buf = vec![];
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut buf);
+        buf.put_slice(&payload);
+        buf
+    }
+
+    // Returns the encoding that is embedded into the parent node, and the hash of the node if it
+    // was added to the batch
+    fn write_reference(&self, batch: &mut WriteBatch) -> (Vec<u8>, Option<B256>) {
+        let encoded = self.encode(batch);
+        self.reference(encoded, batch)
+    }
+
+    fn reference(&self, encoded: Vec<u8>, batch: &mut WriteBatch) -> (Vec<u8>, Option<B256>) {
+        if encoded.len() < 32 || matches!(self, Node::Hash(_)) {
+            return (encoded, None);
+        }
+        let hash = keccak256(&encoded);
+        batch.write(hash, encoded);
+        (alloy_rlp::encode(hash), Some(hash))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Node {
+    /// Same as [Node::encode], but modified children of branch nodes are encoded and hashed in
+    /// parallel if there are enough of them. The encoding is identical to the sequential one.
+    pub fn par_encode(&self, batch: &mut WriteBatch) -> Vec<u8> {
+        match self {
+            Node::Extension(extension_node) => Self::encode_extension(
+                &extension_node.prefix,
+                &extension_node.node.par_write_reference(batch),
+            ),
             Node::Branch(branch_node) => {
-                let mut payload = vec![];
-                for i in 0..16 {
-                    payload.put_slice(&branch_node[i].write(db)?);
+                let modified_children = branch_node
+                    .children
+                    .iter()
+                    .filter(|child| !matches!(child, Node::Nil | Node::Hash(_)))
+                    .count();
+                if modified_children < PARALLEL_WRITE_MIN_CHILDREN {
+                    // Subtrees can still have enough modified children at lower levels
+                    return Self::encode_branch(
+                        branch_node
+                            .children
+                            .iter()
+                            .map(|child| child.par_write_reference(batch)),
+                        &branch_node.value,
+                    );
                 }
-                branch_node.value.as_slice().encode(&mut payload);
-
-                let mut buf = vec![];
-                Header {
-                    list: true,
-                    payload_length: payload.len(),
+                let (child_references, child_batches): (Vec<_>, Vec<_>) = branch_node
+                    .children
+                    .par_iter()
+                    .map(|child| {
+                        let mut child_batch = WriteBatch::new();
+                        let child_reference = child.par_write_reference(&mut child_batch);
+                        (child_reference, child_batch)
+                    })
+                    .unzip();
+                for child_batch in child_batches {
+                    batch.extend(child_batch);
                 }
-                .encode(&mut buf);
-                buf.put_slice(&payload);
-                buf
+                Self::encode_branch(child_references, &branch_node.value)
             }
-        };
-        if encoded.len() < 32 {
-            Ok(encoded)
-        } else {
-            let hash = keccak256(&encoded);
-            db.write(hash, encoded)?;
-            *self = Node::Hash(hash.into());
-            self.write(db)
+            Node::Nil | Node::Leaf(_) | Node::Hash(_) => self.encode(batch),
         }
     }
+
+    fn par_write_reference(&self, batch: &mut WriteBatch) -> Vec<u8> {
+        let encoded = self.par_encode(batch);
+        self.reference(encoded, batch).0
+    }
 }
 
 impl Decodable for Node {
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256, U64};
+use alloy_rlp::Decodable;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::AccountState,
+    nibbles::{Nibble, Nibbles},
+    nodes::{Node, NodeTraversalInfo},
+};
+
+/// Proof of the account, as returned by `eth_getProof`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountProof {
+    pub address: Address,
+    pub account_proof: Vec<Bytes>,
+    pub balance: U256,
+    pub code_hash: B256,
+    pub nonce: U64,
+    pub storage_hash: B256,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// Proof of the storage slot, as returned by `eth_getProof`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageProof {
+    pub key: B256,
+    pub value: U256,
+    pub proof: Vec<Bytes>,
+}
+
+/// Verifies that the proof (RLP encoded nodes along the path, starting from the root) is valid
+/// for the given root.
+///
+/// Returns the value if proof is inclusion proof, or `None` if it's exclusion proof.
+pub fn verify_proof<T: AsRef<[u8]>>(
+    root: B256,
+    path: &[Nibble],
+    proof: &[T],
+) -> Result<Option<Vec<u8>>> {
+    let nodes: HashMap<B256, &[u8]> = proof
+        .iter()
+        .map(|encoded_node| (keccak256(encoded_node), encoded_node.as_ref()))
+        .collect();
+
+    if proof.is_empty() && root == keccak256([alloy_rlp::EMPTY_STRING_CODE]) {
+        return Ok(None);
+    }
+
+    let mut hash = root;
+    let mut remaining_path = path;
+    loop {
+        let Some(mut encoded_node) = nodes.get(&hash).copied() else {
+            bail!("Node missing from proof: {hash:?}")
+        };
+        let node = Node::decode(&mut encoded_node)?;
+        match node.next_node(remaining_path) {
+            NodeTraversalInfo::Empty => return Ok(None),
+            NodeTraversalInfo::Value(value) => return Ok(Some(value.to_vec())),
+            NodeTraversalInfo::NextNode {
+                hash: next_hash,
+                remaining_path: next_remaining_path,
+            } => {
+                hash = next_hash;
+                remaining_path = next_remaining_path;
+            }
+        }
+    }
+}
+
+/// Verifies the account proof (ignoring storage proofs) against the state root.
+pub fn verify_account_proof(state_root: B256, account_proof: &AccountProof) -> Result<()> {
+    let path = Nibbles::from_packed(keccak256(account_proof.address));
+    let account = verify_proof(state_root, &path, &account_proof.account_proof)?
+        .map(|encoded| AccountState::decode(&mut encoded.as_slice()))
+        .transpose()?
+        .unwrap_or_default();
+    if account.nonce != account_proof.nonce.to::<u64>()
+        || account.balance != account_proof.balance
+        || account.storage_root != account_proof.storage_hash
+        || account.code_hash != account_proof.code_hash
+    {
+        bail!("Account doesn't match the proof: {account_proof:?}")
+    }
+    Ok(())
+}
+
+/// Verifies the storage proof against the storage root.
+pub fn verify_storage_proof(storage_root: B256, storage_proof: &StorageProof) -> Result<()> {
+    let path = Nibbles::from_packed(keccak256(storage_proof.key));
+    let value = verify_proof(storage_root, &path, &storage_proof.proof)?
+        .map(|encoded| U256::decode(&mut encoded.as_slice()))
+        .transpose()?
+        .unwrap_or_default();
+    if value != storage_proof.value {
+        bail!(
+            "Storage value doesn't match. Expected: {}, proof: {value}",
+            storage_proof.value
+        )
+    }
+    Ok(())
+}
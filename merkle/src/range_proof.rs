@@ -0,0 +1,376 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use alloy_primitives::{keccak256, Bytes, B256};
+use alloy_rlp::Decodable;
+use anyhow::{bail, ensure, Result};
+use db::memory_db::MemoryDb;
+
+use crate::{
+    account::EMPTY_ROOT_HASH,
+    nibbles::{Nibble, Nibbles},
+    nodes::Node,
+    Db, WriteBatch,
+};
+
+/// The contiguous range of trie entries, together with the proofs of its edges, as used by the
+/// snap protocol.
+///
+/// The proof consists of the nodes along the path to the starting key and the nodes along the
+/// path to the last returned key. It can be empty if the range contains all entries of the trie.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeProof {
+    pub keys: Vec<B256>,
+    pub values: Vec<Vec<u8>>,
+    pub proof: Vec<Bytes>,
+}
+
+/// Verifies that the keys and values are all entries of the trie, starting from `origin` up to
+/// the last key (inclusive).
+///
+/// Returns whether the trie contains more entries after the last key.
+pub fn verify_range_proof<T: AsRef<[u8]>>(
+    root: B256,
+    origin: B256,
+    keys: &[B256],
+    values: &[Vec<u8>],
+    proof: &[T],
+) -> Result<bool> {
+    ensure!(
+        keys.len() == values.len(),
+        "Number of keys ({}) and values ({}) doesn't match",
+        keys.len(),
+        values.len()
+    );
+    ensure!(
+        keys.windows(2).all(|pair| pair[0] < pair[1]),
+        "Keys are not sorted"
+    );
+    ensure!(
+        values.iter().all(|value| !value.is_empty()),
+        "Empty values are not allowed"
+    );
+    ensure!(
+        keys.first().is_none_or(|first| *first >= origin),
+        "First key is smaller than origin"
+    );
+
+    let db = MemoryDb::new();
+    let db: &Db = &db;
+
+    // All entries are present, no edge proofs needed
+    if proof.is_empty() {
+        let mut trie = Node::Nil;
+        for (key, value) in keys.iter().zip(values) {
+            trie.update(&Nibbles::from_packed(key), value.clone(), db)?;
+        }
+        ensure!(
+            hash_of(&mut trie) == root,
+            "Root doesn't match the provided entries"
+        );
+        return Ok(false);
+    }
+
+    let nodes: HashMap<B256, &[u8]> = proof
+        .iter()
+        .map(|encoded_node| (keccak256(encoded_node), encoded_node.as_ref()))
+        .collect();
+
+    let left = Nibbles::from_packed(origin);
+    let right = keys.last().map(Nibbles::from_packed);
+
+    let mut trie = Node::Hash(root.into());
+    resolve_path(&mut trie, &left, &nodes)?;
+    if let Some(right) = &right {
+        resolve_path(&mut trie, right, &nodes)?;
+    }
+
+    unset_range(
+        &mut trie,
+        Some(&left),
+        right.as_ref().map(|right| right.as_slice()),
+    )?;
+    let has_more = match &right {
+        Some(right) => has_right(&trie, right),
+        None => false,
+    };
+
+    // Nodes outside of the range are Hash nodes that can't be loaded from the (empty) Db, so
+    // inserting keys outside of the range fails
+    for (key, value) in keys.iter().zip(values) {
+        trie.update(&Nibbles::from_packed(key), value.clone(), db)?;
+    }
+    ensure!(
+        hash_of(&mut trie) == root,
+        "Root doesn't match the provided entries and proof"
+    );
+    Ok(has_more)
+}
+
+fn hash_of(trie: &mut Node) -> B256 {
+    let encoded = trie.write(&mut WriteBatch::new());
+    match trie {
+        Node::Nil => EMPTY_ROOT_HASH,
+        Node::Hash(hash) => **hash,
+        _ => keccak256(encoded),
+    }
+}
+
+/// Replaces Hash nodes along the path with the nodes from the proof.
+fn resolve_path(node: &mut Node, path: &[Nibble], nodes: &HashMap<B256, &[u8]>) -> Result<()> {
+    match node {
+        Node::Nil | Node::Leaf(_) => Ok(()),
+        Node::Extension(extension_node) => {
+            match path.strip_prefix(extension_node.prefix.as_slice()) {
+                Some(remaining_path) => {
+                    resolve_path(&mut extension_node.node, remaining_path, nodes)
+                }
+                None => Ok(()),
+            }
+        }
+        Node::Branch(branch_node) => match path.split_first() {
+            Some((first, remaining_path)) => {
+                resolve_path(&mut branch_node[**first as usize], remaining_path, nodes)
+            }
+            None => Ok(()),
+        },
+        Node::Hash(hash_node) => {
+            let Some(mut encoded_node) = nodes.get(&**hash_node).copied() else {
+                bail!("Node missing from proof: {:?}", **hash_node)
+            };
+            *node = Node::decode(&mut encoded_node)?;
+            resolve_path(node, path, nodes)
+        }
+    }
+}
+
+/// Removes all entries whose (remaining) path is between `left` and `right` (inclusive). Bound
+/// that is `None` is unbounded.
+fn unset_range(node: &mut Node, left: Option<&[Nibble]>, right: Option<&[Nibble]>) -> Result<()> {
+    if left.is_none() && right.is_none() {
+        *node = Node::Nil;
+        return Ok(());
+    }
+    match node {
+        Node::Nil => {}
+        Node::Leaf(leaf_node) => {
+            let path = leaf_node.prefix.as_slice();
+            if left.is_none_or(|left| path >= left) && right.is_none_or(|right| path <= right) {
+                *node = Node::Nil;
+            }
+        }
+        Node::Extension(extension_node) => {
+            let prefix = extension_node.prefix.as_slice();
+            let Some(left) = child_bound(prefix, left, Ordering::Less) else {
+                return Ok(());
+            };
+            let Some(right) = child_bound(prefix, right, Ordering::Greater) else {
+                return Ok(());
+            };
+            if left.is_none() && right.is_none() {
+                *node = Node::Nil;
+            } else {
+                unset_range(&mut extension_node.node, left, right)?;
+            }
+        }
+        Node::Branch(branch_node) => {
+            // Value has empty (remaining) path
+            if left.is_none_or(|left| left.is_empty()) {
+                branch_node.value.clear();
+            }
+            for (index, child) in branch_node.children.iter_mut().enumerate() {
+                let prefix = [Nibble::try_from(index as u8)?];
+                let Some(left) = child_bound(&prefix, left, Ordering::Less) else {
+                    continue;
+                };
+                let Some(right) = child_bound(&prefix, right, Ordering::Greater) else {
+                    continue;
+                };
+                unset_range(child, left, right)?;
+            }
+        }
+        Node::Hash(hash_node) => bail!("Node missing from proof: {:?}", **hash_node),
+    }
+    Ok(())
+}
+
+/// Returns the bound for the subtree with the given prefix, or `None` if the whole subtree is
+/// outside of the bound (all its paths compare to the bound as `outside`).
+fn child_bound<'a>(
+    prefix: &[Nibble],
+    bound: Option<&'a [Nibble]>,
+    outside: Ordering,
+) -> Option<Option<&'a [Nibble]>> {
+    let Some(bound) = bound else {
+        return Some(None);
+    };
+    if let Some(remaining_bound) = bound.strip_prefix(prefix) {
+        return Some(Some(remaining_bound));
+    }
+    // Paths in the subtree are longer than the bound (which is prefix of them)
+    let bound_prefix = &bound[..bound.len().min(prefix.len())];
+    let ordering = match prefix.cmp(bound_prefix) {
+        Ordering::Equal => Ordering::Greater,
+        ordering => ordering,
+    };
+    if ordering == outside {
+        None
+    } else {
+        Some(None)
+    }
+}
+
+/// Returns whether the trie has entries with path greater than `right`.
+fn has_right(node: &Node, right: &[Nibble]) -> bool {
+    match node {
+        Node::Nil => false,
+        Node::Leaf(leaf_node) => leaf_node.prefix.as_slice() > right,
+        Node::Extension(extension_node) => {
+            match right.strip_prefix(extension_node.prefix.as_slice()) {
+                Some(remaining_right) => has_right(&extension_node.node, remaining_right),
+                None => extension_node.prefix.as_slice() > right,
+            }
+        }
+        Node::Branch(branch_node) => match right.split_first() {
+            Some((first, remaining_right)) => {
+                branch_node.children[**first as usize + 1..]
+                    .iter()
+                    .any(|child| !matches!(child, Node::Nil))
+                    || has_right(&branch_node.children[**first as usize], remaining_right)
+            }
+            None => branch_node
+                .children
+                .iter()
+                .any(|child| !matches!(child, Node::Nil)),
+        },
+        Node::Hash(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::mpt::Mpt;
+
+    use super::*;
+
+    fn init(count: u8) -> Result<(Mpt, BTreeMap<B256, Vec<u8>>)> {
+        let mut tree = Mpt::default();
+        let mut entries = BTreeMap::new();
+        for i in 0..count {
+            let key = keccak256([i]);
+            let value = vec![i; 1 + i as usize % 40];
+            tree.set_raw(&Nibbles::from_packed(key), value.clone())?;
+            entries.insert(key, value);
+        }
+        Ok((tree, entries))
+    }
+
+    #[test]
+    fn ranges() -> Result<()> {
+        let (mut tree, entries) = init(100)?;
+        let root = tree.get_hash()?;
+        let keys = entries.keys().copied().collect::<Vec<_>>();
+
+        for (origin, limit, max_results) in [
+            (B256::ZERO, B256::repeat_byte(0xff), 1000),
+            (B256::ZERO, B256::repeat_byte(0xff), 10),
+            (B256::ZERO, B256::ZERO, 1000),
+            (keys[10], keys[20], 1000),
+            (keys[10], keys[20], 5),
+            (B256::repeat_byte(0x40), B256::repeat_byte(0x80), 1000),
+            (B256::repeat_byte(0x40), B256::repeat_byte(0xff), 1000),
+            (keys[99], B256::repeat_byte(0xff), 1000),
+            (B256::repeat_byte(0xff), B256::repeat_byte(0xff), 1000),
+        ] {
+            let range_proof = tree.get_range_proof(origin, limit, max_results)?;
+            let has_more = verify_range_proof(
+                root,
+                origin,
+                &range_proof.keys,
+                &range_proof.values,
+                &range_proof.proof,
+            )?;
+
+            let expected = entries.range(origin..).collect::<Vec<_>>();
+            assert_eq!(range_proof.keys.len(), {
+                let until_limit = expected.iter().take_while(|(key, _)| **key < limit).count();
+                (until_limit + 1).min(expected.len()).min(max_results)
+            });
+            for (key, value) in range_proof.keys.iter().zip(&range_proof.values) {
+                assert_eq!(entries.get(key), Some(value));
+            }
+            assert_eq!(has_more, range_proof.keys.len() < expected.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn whole_trie_without_proof() -> Result<()> {
+        let (mut tree, entries) = init(100)?;
+        let root = tree.get_hash()?;
+        let keys = entries.keys().copied().collect::<Vec<_>>();
+        let values = entries.values().cloned().collect::<Vec<_>>();
+
+        let no_proof: &[Bytes] = &[];
+        assert!(!verify_range_proof(
+            root,
+            B256::ZERO,
+            &keys,
+            &values,
+            no_proof
+        )?);
+        assert!(verify_range_proof(root, B256::ZERO, &keys[1..], &values[1..], no_proof).is_err());
+
+        let empty_root = Mpt::default().get_hash()?;
+        assert!(!verify_range_proof(
+            empty_root,
+            B256::ZERO,
+            &[],
+            &[],
+            no_proof
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_ranges() -> Result<()> {
+        let (mut tree, _) = init(100)?;
+        let root = tree.get_hash()?;
+        let origin = B256::repeat_byte(0x40);
+        let range_proof = tree.get_range_proof(origin, B256::repeat_byte(0xc0), 1000)?;
+        let RangeProof {
+            keys,
+            values,
+            proof,
+        } = range_proof;
+        assert!(keys.len() > 10);
+
+        // Missing key in the middle
+        let mut missing_keys = keys.clone();
+        let mut missing_values = values.clone();
+        missing_keys.remove(5);
+        missing_values.remove(5);
+        assert!(verify_range_proof(root, origin, &missing_keys, &missing_values, &proof).is_err());
+
+        // Missing first key
+        assert!(verify_range_proof(root, origin, &keys[1..], &values[1..], &proof).is_err());
+
+        // Modified value
+        let mut modified_values = values.clone();
+        modified_values[5].push(0);
+        assert!(verify_range_proof(root, origin, &keys, &modified_values, &proof).is_err());
+
+        // Unsorted keys
+        let mut unsorted_keys = keys.clone();
+        unsorted_keys.swap(1, 2);
+        assert!(verify_range_proof(root, origin, &unsorted_keys, &values, &proof).is_err());
+
+        // Claiming no entries
+        assert!(verify_range_proof(root, origin, &[], &[], &proof).is_err());
+
+        // Wrong root
+        assert!(verify_range_proof(B256::ZERO, origin, &keys, &values, &proof).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use alloy_primitives::B256;
+use anyhow::{bail, Result};
+use db::memory_db::MemoryDb;
+
+use crate::{
+    mpt::Mpt,
+    nibbles::{Nibble, Nibbles},
+    nodes::Node,
+    Db, WriteBatch,
+};
+
+/// Builds the trie from entries inserted in strictly increasing order of paths.
+///
+/// Subtrees to the left of the last inserted path can't change anymore, so they are hashed and
+/// written to the Db right away. Only the nodes along the last inserted path are kept in memory.
+pub struct StackTrie {
+    root: Node,
+    db: Box<Db>,
+    last_path: Option<Nibbles>,
+}
+
+impl StackTrie {
+    pub fn new(db: Box<Db>) -> Self {
+        Self {
+            root: Node::Nil,
+            db,
+            last_path: None,
+        }
+    }
+
+    pub fn insert(&mut self, path: &[Nibble], value: Vec<u8>) -> Result<()> {
+        if let Some(last_path) = &self.last_path {
+            if path <= last_path.as_slice() {
+                bail!(
+                    "Paths should be inserted in increasing order: {last_path:?} followed by {:?}",
+                    Nibbles::from_slice(path)
+                );
+            }
+        }
+        self.root.update(path, value, &*self.db)?;
+        self.last_path = Some(Nibbles::from_slice(path));
+
+        let mut batch = WriteBatch::new();
+        Self::write_left(&mut self.root, path, &mut batch);
+        self.db.write_batch(batch)?;
+        Ok(())
+    }
+
+    /// Writes the remaining nodes to the Db and returns the root hash.
+    pub fn root(&mut self) -> Result<B256> {
+        let mut batch = WriteBatch::new();
+        let hash = Mpt::write_root(&self.root, &mut batch);
+        self.db.write_batch(batch)?;
+        Ok(hash)
+    }
+
+    /// Writes all children that are to the left of the path.
+    fn write_left(node: &mut Node, path: &[Nibble], batch: &mut WriteBatch) {
+        match node {
+            Node::Nil | Node::Leaf(_) | Node::Hash(_) => {}
+            Node::Extension(extension_node) => {
+                if let Some(remaining_path) = path.strip_prefix(extension_node.prefix.as_slice()) {
+                    Self::write_left(&mut extension_node.node, remaining_path, batch);
+                }
+            }
+            Node::Branch(branch_node) => {
+                let Some((first, remaining_path)) = path.split_first() else {
+                    return;
+                };
+                let index = **first as usize;
+                for child in &mut branch_node.children[..index] {
+                    if !matches!(child, Node::Nil | Node::Hash(_)) {
+                        child.write(batch);
+                    }
+                }
+                Self::write_left(&mut branch_node[index], remaining_path, batch);
+            }
+        }
+    }
+}
+
+impl Default for StackTrie {
+    fn default() -> Self {
+        Self::new(Box::new(MemoryDb::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::keccak256;
+
+    use super::*;
+
+    #[test]
+    fn empty() -> Result<()> {
+        assert_eq!(StackTrie::default().root()?, Mpt::default().get_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn same_root_as_mpt() -> Result<()> {
+        for count in [1u16, 2, 10, 100, 1000] {
+            let mut entries = (0..count)
+                .map(|i| {
+                    (
+                        keccak256(i.to_be_bytes()).to_vec(),
+                        vec![i as u8; 1 + i as usize % 40],
+                    )
+                })
+                .collect::<Vec<_>>();
+            // Paths of different lengths, some being prefixes of others
+            entries.extend(
+                entries[..count as usize / 2]
+                    .iter()
+                    .map(|(path, value)| (path[..1 + path[0] as usize % 4].to_vec(), value.clone()))
+                    .collect::<Vec<_>>(),
+            );
+            entries.sort();
+            entries.dedup_by(|a, b| a.0 == b.0);
+
+            let mut stack_trie = StackTrie::default();
+            let mut mpt = Mpt::default();
+            for (path, value) in &entries {
+                let path = Nibbles::from_packed(path);
+                stack_trie.insert(&path, value.clone())?;
+                mpt.set_raw(&path, value.clone())?;
+            }
+            assert_eq!(stack_trie.root()?, mpt.get_hash()?, "count: {count}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unordered_insert() -> Result<()> {
+        let mut stack_trie = StackTrie::default();
+        stack_trie.insert(&Nibbles::from_packed(b"dog"), b"puppy".to_vec())?;
+        assert!(stack_trie
+            .insert(&Nibbles::from_packed(b"do"), b"verb".to_vec())
+            .is_err());
+        assert!(stack_trie
+            .insert(&Nibbles::from_packed(b"dog"), b"puppy".to_vec())
+            .is_err());
+        Ok(())
+    }
+}
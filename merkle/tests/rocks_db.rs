@@ -0,0 +1,40 @@
+#[cfg(all(test, feature = "rocksdb"))]
+mod rocks_db {
+    use alloy_primitives::{Address, B256, U256};
+    use anyhow::Result;
+    use db::rocks_db::RocksDb;
+    use merkle::{account::AccountState, mpt::Mpt};
+    use tempfile::TempDir;
+
+    #[test]
+    fn reopen() -> Result<()> {
+        let dir = TempDir::new()?;
+        let contract = Address::repeat_byte(0xff);
+        let slot = B256::with_last_byte(1);
+        let code = vec![0x60, 0x00, 0x60, 0x00, 0xf3];
+
+        let root = {
+            let mut tree = Mpt::new(Box::new(RocksDb::open(dir.path())?));
+            for i in 0u8..100 {
+                tree.set_account(
+                    Address::repeat_byte(i),
+                    &AccountState::new_eoa(U256::from(i)),
+                )?;
+            }
+            tree.set_code(contract, code.clone())?;
+            tree.set_storage(contract, slot, U256::from(12345))?;
+            tree.get_hash()?
+        };
+
+        let mut tree = Mpt::new_with_root(root, Box::new(RocksDb::open(dir.path())?));
+        for i in 0u8..100 {
+            let account = tree.get_account(&Address::repeat_byte(i))?;
+            assert_eq!(account.map(|account| account.balance), Some(U256::from(i)));
+        }
+        assert_eq!(tree.get_code(&contract)?, Some(code));
+        assert_eq!(tree.get_storage(&contract, slot)?, U256::from(12345));
+        assert!(tree.get_account(&Address::with_last_byte(1))?.is_none());
+        assert_eq!(tree.get_hash()?, root);
+        Ok(())
+    }
+}
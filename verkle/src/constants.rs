@@ -9,6 +9,11 @@ pub const BALANCE_LEAF_KEY: u8 = 1;
 pub const NONCE_LEAF_KEY: u8 = 2;
 pub const CODE_KECCAK_LEAF_KEY: u8 = 3;
 pub const CODE_SIZE_LEAF_KEY: u8 = 4;
+pub const BASIC_DATA_LEAF_KEY: u8 = 0;
+pub const CODE_HASH_LEAF_KEY: u8 = 1;
+pub const BASIC_DATA_CODE_SIZE_OFFSET: usize = 5;
+pub const BASIC_DATA_NONCE_OFFSET: usize = 8;
+pub const BASIC_DATA_BALANCE_OFFSET: usize = 16;
 pub const HEADER_STORAGE_OFFSET: U256 = U256::from_limbs([64, 0, 0, 0]);
 pub const CODE_OFFSET: U256 = U256::from_limbs([128, 0, 0, 0]);
 pub const MAIN_STORAGE_OFFSET: U256 = U256::from_limbs([0, 0, 0, 2u64.pow(56)]);
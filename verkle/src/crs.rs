@@ -9,6 +9,10 @@ const PEDERSEN_SEED: &[u8] = b"eth_verkle_oct_2021";
 
 pub static CRS: Lazy<Bases> = Lazy::new(Bases::new);
 
+/// The CRS used for creating and verifying IPA proofs (same bases, extended with point Q).
+pub static IPA_CRS: Lazy<ipa_multipoint::crs::CRS> =
+    Lazy::new(|| ipa_multipoint::crs::CRS::new(VERKLE_NODE_WIDTH, PEDERSEN_SEED));
+
 #[derive(AsRef, Deref, Index)]
 pub struct Bases([Element; VERKLE_NODE_WIDTH]);
 
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+
+use alloy_primitives::B256;
+use anyhow::Result;
+
+use crate::{
+    iterator::{StemEntry, StemIterator},
+    nodes::{CommitmentNode, Node, NodeTrait},
+    utils::b256_to_element,
+    Db, TrieKey, TrieValue,
+};
+
+/// The change of the value stored at some key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueDiff {
+    Added(TrieValue),
+    Removed(TrieValue),
+    Changed { old: TrieValue, new: TrieValue },
+}
+
+/// Returns all changes between the tries with the given roots, in the ascending order of keys.
+///
+/// Both tries are walked together and subtrees with the same commitment are skipped, so only
+/// nodes on the paths to the changed values are loaded from the Db.
+pub fn diff(old_root: B256, new_root: B256, db: &Db) -> Result<Vec<(TrieKey, ValueDiff)>> {
+    let mut result = vec![];
+    diff_nodes(
+        0,
+        Some(root_node(old_root)),
+        Some(root_node(new_root)),
+        db,
+        &mut result,
+    )?;
+    Ok(result)
+}
+
+fn root_node(root: B256) -> Node {
+    Node::Commitment(CommitmentNode::new(b256_to_element(&root)))
+}
+
+fn diff_nodes(
+    depth: usize,
+    old: Option<Node>,
+    new: Option<Node>,
+    db: &Db,
+    result: &mut Vec<(TrieKey, ValueDiff)>,
+) -> Result<()> {
+    let (mut old, mut new) = match (old, new) {
+        (None, None) => return Ok(()),
+        (Some(Node::Commitment(old)), Some(Node::Commitment(new)))
+            if old.commitment() == new.commitment() =>
+        {
+            return Ok(())
+        }
+        (old, new) => (old, new),
+    };
+    for node in old.iter_mut().chain(new.iter_mut()) {
+        node.resolve(db)?;
+    }
+
+    match (old, new) {
+        (Some(Node::Branch(old)), Some(new)) => {
+            diff_children(depth, old.into_children(), children(depth, new), db, result)
+        }
+        (Some(old), Some(Node::Branch(new))) => {
+            diff_children(depth, children(depth, old), new.into_children(), db, result)
+        }
+        // Remaining subtrees have at most one stem on at least one side
+        (old, new) => {
+            diff_stems(stems(old, db)?, stems(new, db)?, result);
+            Ok(())
+        }
+    }
+}
+
+fn diff_children(
+    depth: usize,
+    mut old: BTreeMap<u8, Node>,
+    mut new: BTreeMap<u8, Node>,
+    db: &Db,
+    result: &mut Vec<(TrieKey, ValueDiff)>,
+) -> Result<()> {
+    let mut indices = old.keys().chain(new.keys()).copied().collect::<Vec<_>>();
+    indices.sort();
+    indices.dedup();
+    for index in indices {
+        diff_nodes(
+            depth + 1,
+            old.remove(&index),
+            new.remove(&index),
+            db,
+            result,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the children of the resolved branch node, or the leaf node as the only child.
+fn children(depth: usize, node: Node) -> BTreeMap<u8, Node> {
+    match node {
+        Node::Branch(branch_node) => branch_node.into_children(),
+        Node::Leaf(leaf_node) => BTreeMap::from([(leaf_node.stem()[depth], Node::Leaf(leaf_node))]),
+        Node::Commitment(_) => unreachable!("Node should be resolved"),
+    }
+}
+
+fn stems(node: Option<Node>, db: &Db) -> Result<Vec<StemEntry>> {
+    match node {
+        Some(node) => StemIterator::new(&node, db).collect(),
+        None => Ok(vec![]),
+    }
+}
+
+fn diff_stems(old: Vec<StemEntry>, new: Vec<StemEntry>, result: &mut Vec<(TrieKey, ValueDiff)>) {
+    let key_values = |stems: Vec<StemEntry>| {
+        stems
+            .into_iter()
+            .flat_map(|(stem, values)| {
+                values.into_iter().map(move |(suffix, value)| {
+                    (TrieKey::from_stem_and_last_byte(&stem, suffix), value)
+                })
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let old_values = key_values(old);
+    let new_values = key_values(new);
+
+    let mut keys = old_values
+        .keys()
+        .chain(new_values.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let value_diff = match (old_values.get(&key), new_values.get(&key)) {
+            (None, Some(new)) => ValueDiff::Added(*new),
+            (Some(old), None) => ValueDiff::Removed(*old),
+            (Some(old), Some(new)) if old != new => ValueDiff::Changed {
+                old: *old,
+                new: *new,
+            },
+            _ => continue,
+        };
+        result.push((key, value_diff));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+    use ark_ff::UniformRand;
+    use db::memory_db::MemoryDb;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::Trie;
+
+    use super::*;
+
+    type Entries = BTreeMap<TrieKey, TrieValue>;
+
+    fn expected_diff(old: &Entries, new: &Entries) -> Vec<(TrieKey, ValueDiff)> {
+        let mut result = vec![];
+        diff_stems(
+            old.iter()
+                .map(|(key, value)| (key.stem(), BTreeMap::from([(key.last(), *value)])))
+                .collect(),
+            new.iter()
+                .map(|(key, value)| (key.stem(), BTreeMap::from([(key.last(), *value)])))
+                .collect(),
+            &mut result,
+        );
+        result
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let mut trie = Trie::new(Box::new(MemoryDb::new()));
+        let root = trie.root()?;
+        assert_eq!(trie.diff(root, root)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn random_changes() -> Result<()> {
+        let mut trie = Trie::new(Box::new(MemoryDb::new()));
+        let mut rng = StdRng::seed_from_u64(12345);
+
+        let mut keys = vec![];
+        let mut entries = Entries::new();
+        let mut roots = vec![(trie.root()?, entries.clone())];
+        for _ in 0..5 {
+            for _ in 0..100 {
+                // Reuse existing keys and stems, so that values are also changed and removed
+                let key = match rng.gen_range(0..4) {
+                    0 if !keys.is_empty() => keys[rng.gen_range(0..keys.len())],
+                    1 if !keys.is_empty() => {
+                        let key: TrieKey = keys[rng.gen_range(0..keys.len())];
+                        TrieKey::from_stem_and_last_byte(&key.stem(), rng.gen())
+                    }
+                    _ => TrieKey::new(B256::random_with(&mut rng)),
+                };
+                keys.push(key);
+                if rng.gen_bool(0.2) {
+                    trie.remove(key)?;
+                    entries.remove(&key);
+                } else {
+                    let value = U256::rand(&mut rng);
+                    trie.insert(key, value)?;
+                    entries.insert(key, value);
+                }
+            }
+            roots.push((trie.root()?, entries.clone()));
+        }
+
+        for (old_root, old_entries) in &roots {
+            for (new_root, new_entries) in &roots {
+                assert_eq!(
+                    trie.diff(*old_root, *new_root)?,
+                    expected_diff(old_entries, new_entries)
+                );
+            }
+        }
+        Ok(())
+    }
+}
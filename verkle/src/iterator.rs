@@ -0,0 +1,249 @@
+use std::collections::{btree_map, BTreeMap};
+
+use alloy_primitives::B256;
+use anyhow::Result;
+
+use crate::{nodes::Node, stem::Stem, Db, TrieKey, TrieValue};
+
+/// The stem and all its values.
+pub type StemEntry = (Stem, BTreeMap<u8, TrieValue>);
+
+enum NodeRef<'a> {
+    Borrowed(&'a Node),
+    /// Node loaded from the Db.
+    Owned(Node),
+}
+
+/// Iterates over stems and their values, in the ascending order of stems.
+///
+/// Commitment nodes are loaded from the Db lazily, only when iteration reaches them.
+pub struct StemIterator<'a> {
+    root: &'a Node,
+    db: &'a Db,
+    stack: Vec<(Vec<u8>, NodeRef<'a>)>,
+    start: Vec<u8>,
+}
+
+impl<'a> StemIterator<'a> {
+    pub(crate) fn new(root: &'a Node, db: &'a Db) -> Self {
+        Self {
+            root,
+            db,
+            stack: vec![(vec![], NodeRef::Borrowed(root))],
+            start: vec![],
+        }
+    }
+
+    /// Restarts the iteration from the first stem that is not smaller than `start`.
+    pub fn seek(&mut self, start: &Stem) {
+        self.start = start.to_vec();
+        self.stack = vec![];
+        self.push(vec![], NodeRef::Borrowed(self.root));
+    }
+
+    fn push(&mut self, path: Vec<u8>, node: NodeRef<'a>) {
+        // All stems in the subtree are smaller than start
+        if path < self.start && !self.start.starts_with(&path) {
+            return;
+        }
+        self.stack.push((path, node));
+    }
+
+    fn next_entry(&mut self) -> Result<Option<StemEntry>> {
+        while let Some((path, node)) = self.stack.pop() {
+            let leaf_node = match node {
+                NodeRef::Borrowed(node) => match node {
+                    Node::Branch(branch_node) => {
+                        // Pushed in reverse order, so that the first child is processed first
+                        for (index, child) in branch_node.children().iter().rev() {
+                            self.push(
+                                [path.as_slice(), &[*index]].concat(),
+                                NodeRef::Borrowed(child),
+                            );
+                        }
+                        continue;
+                    }
+                    Node::Leaf(leaf_node) => (*leaf_node.stem(), leaf_node.values().clone()),
+                    Node::Commitment(commitment_node) => {
+                        let node = Node::load(commitment_node, self.db)?;
+                        self.push(path, NodeRef::Owned(node));
+                        continue;
+                    }
+                },
+                NodeRef::Owned(node) => match node {
+                    Node::Branch(branch_node) => {
+                        for (index, child) in branch_node.into_children().into_iter().rev() {
+                            self.push([path.as_slice(), &[index]].concat(), NodeRef::Owned(child));
+                        }
+                        continue;
+                    }
+                    Node::Leaf(leaf_node) => (*leaf_node.stem(), leaf_node.into_values()),
+                    Node::Commitment(commitment_node) => {
+                        let node = Node::load(&commitment_node, self.db)?;
+                        self.push(path, NodeRef::Owned(node));
+                        continue;
+                    }
+                },
+            };
+            if leaf_node.0.as_slice() >= self.start.as_slice() {
+                return Ok(Some(leaf_node));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for StemIterator<'_> {
+    type Item = Result<StemEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(err) => {
+                self.stack.clear();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Iterates over `(key, value)` pairs, in the ascending order of keys.
+pub struct TrieIterator<'a> {
+    stems: StemIterator<'a>,
+    current: Option<(Stem, btree_map::IntoIter<u8, TrieValue>)>,
+    start: TrieKey,
+}
+
+impl<'a> TrieIterator<'a> {
+    pub(crate) fn new(root: &'a Node, db: &'a Db) -> Self {
+        Self {
+            stems: StemIterator::new(root, db),
+            current: None,
+            start: TrieKey::new(B256::ZERO),
+        }
+    }
+
+    /// Restarts the iteration from the first key that is not smaller than `start`.
+    pub fn seek(&mut self, start: TrieKey) {
+        self.stems.seek(&start.stem());
+        self.current = None;
+        self.start = start;
+    }
+}
+
+impl Iterator for TrieIterator<'_> {
+    type Item = Result<(TrieKey, TrieValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((stem, values)) = &mut self.current {
+                for (suffix, value) in values.by_ref() {
+                    let key = TrieKey::from_stem_and_last_byte(stem, suffix);
+                    if key >= self.start {
+                        return Some(Ok((key, value)));
+                    }
+                }
+            }
+            match self.stems.next()? {
+                Ok((stem, values)) => self.current = Some((stem, values.into_iter())),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+    use ark_ff::UniformRand;
+    use db::memory_db::MemoryDb;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::Trie;
+
+    use super::*;
+
+    type Entries = BTreeMap<TrieKey, TrieValue>;
+
+    fn init(count: usize) -> Result<(Trie, Entries)> {
+        let mut trie = Trie::new(Box::new(MemoryDb::new()));
+        let mut rng = StdRng::seed_from_u64(12345);
+
+        let mut expected = BTreeMap::new();
+        for _ in 0..count {
+            let key = TrieKey::new(B256::random_with(&mut rng));
+            // Also add key with the same stem
+            let same_stem_key = TrieKey::from_stem_and_last_byte(&key.stem(), key.last() / 2);
+            for key in [key, same_stem_key] {
+                let value = U256::rand(&mut rng);
+                trie.insert(key, value)?;
+                expected.insert(key, value);
+            }
+        }
+        Ok((trie, expected))
+    }
+
+    #[test]
+    fn empty() -> Result<()> {
+        let trie = Trie::new(Box::new(MemoryDb::new()));
+        assert_eq!(trie.iter().count(), 0);
+        assert_eq!(trie.iter_stems().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_all() -> Result<()> {
+        let (mut trie, expected) = init(100)?;
+        let expected = expected.into_iter().collect::<Vec<_>>();
+
+        assert_eq!(trie.iter().collect::<Result<Vec<_>>>()?, expected);
+        // After commit, nodes are loaded from the Db
+        trie.root()?;
+        assert_eq!(trie.iter().collect::<Result<Vec<_>>>()?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_stems() -> Result<()> {
+        let (mut trie, expected) = init(100)?;
+        trie.root()?;
+
+        let mut expected_stems = BTreeMap::<Stem, BTreeMap<u8, TrieValue>>::new();
+        for (key, value) in expected {
+            expected_stems
+                .entry(key.stem())
+                .or_default()
+                .insert(key.last(), value);
+        }
+        assert_eq!(
+            trie.iter_stems().collect::<Result<Vec<_>>>()?,
+            expected_stems.into_iter().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn seek() -> Result<()> {
+        let (mut trie, expected) = init(100)?;
+        trie.root()?;
+
+        let keys = expected.keys().copied().collect::<Vec<_>>();
+        for start in [
+            keys[0],
+            keys[50],
+            TrieKey::from_stem_and_last_byte(&keys[50].stem(), 0),
+            TrieKey::from_stem_and_last_byte(&keys[50].stem(), u8::MAX),
+            TrieKey::new(B256::repeat_byte(0x80)),
+            TrieKey::new(B256::repeat_byte(0xff)),
+        ] {
+            let mut iterator = trie.iter();
+            iterator.seek(start);
+            let expected_range = expected
+                .range(start..)
+                .map(|(key, value)| (*key, *value))
+                .collect::<Vec<_>>();
+            assert_eq!(iterator.collect::<Result<Vec<_>>>()?, expected_range);
+        }
+        Ok(())
+    }
+}
@@ -8,7 +8,10 @@ pub use trie::Trie;
 mod committer;
 mod constants;
 pub mod crs;
+pub mod diff;
+pub mod iterator;
 pub mod nodes;
+pub mod proof;
 pub mod stem;
 pub mod storage;
 pub mod trie;
@@ -17,8 +20,11 @@ mod utils;
 pub type TrieValue = U256;
 
 type Db = dyn db::Db<Element, Vec<u8>>;
+type WriteBatch = db::WriteBatch<Element, Vec<u8>>;
 
-#[derive(PartialEq, Eq, Clone, Copy, Constructor, Index, Deref, From)]
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Constructor, Index, Deref, From,
+)]
 pub struct TrieKey(B256);
 
 impl TrieKey {
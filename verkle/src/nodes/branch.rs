@@ -2,17 +2,25 @@ use std::collections::BTreeMap;
 
 use alloy_primitives::B256;
 use anyhow::Result;
-use banderwagon::{Element, Fr};
+use banderwagon::{Element, Fr, Zero};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use ssz::{Decode, Encode};
 
 use crate::{
     committer::DEFAULT_COMMITER,
+    constants::VERKLE_NODE_WIDTH,
+    iterator::StemEntry,
     utils::{b256_to_element, element_to_b256},
-    Db, TrieKey, TrieValue,
+    Db, TrieKey, TrieValue, WriteBatch,
 };
 
 use super::{node::NodeTrait, CommitmentNode, LeafNode, Node};
 
+/// The minimal number of modified children for which it's worth committing them in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_COMMIT_MIN_CHILDREN: usize = 8;
+
 pub struct BranchNode {
     values: BTreeMap<u8, Node>,
     commitment: Element,
@@ -36,10 +44,27 @@ impl BranchNode {
         );
     }
 
-    pub(super) fn get_mut(&mut self, index: u8) -> Option<&mut Node> {
+    pub(crate) fn children(&self) -> &BTreeMap<u8, Node> {
+        &self.values
+    }
+
+    pub(crate) fn into_children(self) -> BTreeMap<u8, Node> {
+        self.values
+    }
+
+    pub(crate) fn get_mut(&mut self, index: u8) -> Option<&mut Node> {
         self.values.get_mut(&index)
     }
 
+    /// Evaluations of the polynomial committed by the branch commitment.
+    pub(crate) fn evaluations(&self) -> Vec<Fr> {
+        let mut evaluations = vec![Fr::zero(); VERKLE_NODE_WIDTH];
+        for (index, node) in &self.values {
+            evaluations[*index as usize] = node.commitment_hash();
+        }
+        evaluations
+    }
+
     pub fn insert(&mut self, depth: usize, key: TrieKey, value: TrieValue, db: &Db) -> Result<()> {
         let index = key[depth];
         let pre_commitment = self.get_child_commit(index);
@@ -56,6 +81,63 @@ impl BranchNode {
         Ok(())
     }
 
+    /// Inserts values of all stems, recomputing the commitment once for all touched children.
+    ///
+    /// All stems must share the first `depth` bytes with this node's path.
+    pub(crate) fn insert_stems(
+        &mut self,
+        depth: usize,
+        stems: Vec<StemEntry>,
+        db: &Db,
+    ) -> Result<()> {
+        let mut groups = BTreeMap::<u8, Vec<StemEntry>>::new();
+        for (stem, values) in stems {
+            groups.entry(stem[depth]).or_default().push((stem, values));
+        }
+
+        let mut commitment_diff = Vec::with_capacity(groups.len());
+        for (index, stems) in groups {
+            let pre_commitment = self.get_child_commit(index);
+            match self.values.get_mut(&index) {
+                Some(node) => node.insert_stems(depth + 1, stems, db)?,
+                None => {
+                    let mut stems = stems.into_iter();
+                    let Some((stem, values)) = stems.next() else {
+                        continue;
+                    };
+                    let mut leaf_node = LeafNode::new(stem);
+                    leaf_node.set_all(values);
+                    let mut node = Node::Leaf(leaf_node);
+                    // Remaining stems split the new leaf into branch
+                    node.insert_stems(depth + 1, stems.collect(), db)?;
+                    self.values.insert(index, node);
+                }
+            }
+            let post_commitment = self.get_child_commit(index);
+            commitment_diff.push((index as usize, post_commitment - pre_commitment));
+        }
+        self.commitment += DEFAULT_COMMITER.commit_sparse(commitment_diff);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, depth: usize, key: TrieKey, db: &Db) -> Result<()> {
+        let index = key[depth];
+        let pre_commitment = self.get_child_commit(index);
+        let Some(node) = self.values.get_mut(&index) else {
+            return Ok(());
+        };
+        node.remove(depth + 1, key, db)?;
+        if node.is_empty() {
+            self.values.remove(&index);
+        }
+        self.update_commitment(index, pre_commitment);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
     fn get_child_commit(&mut self, index: u8) -> Fr {
         self.values
             .get_mut(&index)
@@ -69,11 +151,41 @@ impl BranchNode {
             DEFAULT_COMMITER.scalar_mul(index as usize, post_commitment - pre_commitment);
     }
 
-    pub fn write_and_commit(&mut self, db: &mut Db) -> Result<Element> {
+    pub fn write_and_commit(&mut self, batch: &mut WriteBatch) -> Element {
         for (_, node) in self.values.iter_mut() {
-            node.write_and_commit(db)?;
+            node.write_and_commit(batch);
+        }
+        self.commitment_write()
+    }
+
+    /// Same as [BranchNode::write_and_commit], but modified children are committed in parallel if
+    /// there are enough of them.
+    #[cfg(feature = "rayon")]
+    pub fn par_write_and_commit(&mut self, batch: &mut WriteBatch) -> Element {
+        let modified_children = self
+            .values
+            .values()
+            .filter(|node| !matches!(node, Node::Commitment(_)))
+            .count();
+        if modified_children < PARALLEL_COMMIT_MIN_CHILDREN {
+            for (_, node) in self.values.iter_mut() {
+                node.par_write_and_commit(batch);
+            }
+        } else {
+            let child_batches = self
+                .values
+                .par_iter_mut()
+                .map(|(_, node)| {
+                    let mut child_batch = WriteBatch::new();
+                    node.par_write_and_commit(&mut child_batch);
+                    child_batch
+                })
+                .collect::<Vec<_>>();
+            for child_batch in child_batches {
+                batch.extend(child_batch);
+            }
         }
-        Ok(self.commitment_write())
+        self.commitment_write()
     }
 }
 
@@ -63,6 +63,14 @@ impl LeafNode {
         &self.stem
     }
 
+    pub fn values(&self) -> &BTreeMap<u8, TrieValue> {
+        &self.values
+    }
+
+    pub(crate) fn into_values(self) -> BTreeMap<u8, TrieValue> {
+        self.values
+    }
+
     fn calculate_commitment(&self) -> Element {
         self.const_c
             + DEFAULT_COMMITER.commit_sparse(vec![
@@ -75,18 +83,34 @@ impl LeafNode {
         self.values.get(&index)
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
     pub fn set(&mut self, index: u8, value: TrieValue) {
         let old_value = self.values.insert(index, value);
+        self.update_commitment(index, old_value.as_ref(), Some(&value));
+    }
 
-        let index = index as usize;
+    pub fn remove(&mut self, index: u8) -> Option<TrieValue> {
+        let old_value = self.values.remove(&index);
+        if old_value.is_some() {
+            self.update_commitment(index, old_value.as_ref(), None);
+        }
+        old_value
+    }
 
-        // update commitments
+    fn update_commitment(
+        &mut self,
+        index: u8,
+        old_value: Option<&TrieValue>,
+        value: Option<&TrieValue>,
+    ) {
+        let index = index as usize;
 
-        let (value_low_16, value_high_16) = Self::value_low_high_16(&value);
-        let (old_value_low_16, old_value_high_16) = old_value
-            .map_or((Fr::zero(), Fr::zero()), |old_value| {
-                Self::value_low_high_16(&old_value)
-            });
+        // Missing value contributes nothing (not even the leaf marker) to the commitment
+        let (value_low_16, value_high_16) = Self::value_evaluations(value);
+        let (old_value_low_16, old_value_high_16) = Self::value_evaluations(old_value);
 
         let low_index = index % (VERKLE_NODE_WIDTH / 2) * 2;
         let high_index = low_index + 1;
@@ -102,10 +126,74 @@ impl LeafNode {
         self.commitment = None;
     }
 
+    /// Sets all values at once, updating c1 and c2 with a single commitment each.
     pub fn set_all(&mut self, values: impl IntoIterator<Item = (u8, TrieValue)>) {
+        let half = VERKLE_NODE_WIDTH / 2;
+        let mut c1_diff = BTreeMap::<usize, Fr>::new();
+        let mut c2_diff = BTreeMap::<usize, Fr>::new();
         for (index, value) in values {
-            self.set(index, value)
+            let old_value = self.values.insert(index, value);
+            let (value_low_16, value_high_16) = Self::value_evaluations(Some(&value));
+            let (old_value_low_16, old_value_high_16) = Self::value_evaluations(old_value.as_ref());
+
+            let low_index = index as usize % half * 2;
+            let diff = if (index as usize) < half {
+                &mut c1_diff
+            } else {
+                &mut c2_diff
+            };
+            *diff.entry(low_index).or_default() += value_low_16 - old_value_low_16;
+            *diff.entry(low_index + 1).or_default() += value_high_16 - old_value_high_16;
+        }
+
+        if !c1_diff.is_empty() {
+            self.c1 += DEFAULT_COMMITER.commit_sparse(c1_diff.into_iter().collect());
+            self.commitment = None;
+        }
+        if !c2_diff.is_empty() {
+            self.c2 += DEFAULT_COMMITER.commit_sparse(c2_diff.into_iter().collect());
+            self.commitment = None;
+        }
+    }
+
+    /// Evaluations of the polynomial committed by the leaf commitment.
+    pub(crate) fn evaluations(&self) -> Vec<Fr> {
+        let mut evaluations = vec![Fr::zero(); VERKLE_NODE_WIDTH];
+        evaluations[0] = Fr::one();
+        evaluations[1] = Fr::from_le_bytes_mod_order(self.stem.as_slice());
+        evaluations[2] = self.c1.map_to_scalar_field();
+        evaluations[3] = self.c2.map_to_scalar_field();
+        evaluations
+    }
+
+    /// Returns c1 or c2, depending on which one commits to the value at the given index.
+    pub(crate) fn suffix_commitment(&self, index: u8) -> Element {
+        if (index as usize) < VERKLE_NODE_WIDTH / 2 {
+            self.c1
+        } else {
+            self.c2
+        }
+    }
+
+    /// Evaluations of the polynomial committed by c1 or c2, depending on which one commits to the
+    /// value at the given index.
+    pub(crate) fn suffix_evaluations(&self, index: u8) -> Vec<Fr> {
+        let half = VERKLE_NODE_WIDTH / 2;
+        let start = index as usize / half * half;
+
+        let mut evaluations = vec![Fr::zero(); VERKLE_NODE_WIDTH];
+        for (index, value) in self.values.range(start as u8..=(start + half - 1) as u8) {
+            let low_index = *index as usize % half * 2;
+            let (value_low_16, value_high_16) = Self::value_low_high_16(value);
+            evaluations[low_index] = value_low_16;
+            evaluations[low_index + 1] = value_high_16;
         }
+        evaluations
+    }
+
+    /// Evaluations of c1 or c2 polynomial at the two points that correspond to the value.
+    pub(crate) fn value_evaluations(value: Option<&TrieValue>) -> (Fr, Fr) {
+        value.map_or((Fr::zero(), Fr::zero()), Self::value_low_high_16)
     }
 
     fn value_low_high_16(value: &TrieValue) -> (Fr, Fr) {
@@ -238,4 +326,29 @@ mod tests {
             "0xcc30be1f0d50eacfacaa3361b8df4d2014a849854a6cf35e6c55e07d6963f519"
         );
     }
+
+    #[test]
+    fn remove() {
+        let key0 = TrieKey::new(B256::ZERO);
+        let key1 = TrieKey::new(U256::from(1).into());
+        let key_max = TrieKey::from_stem_and_last_byte(&key0.stem(), u8::MAX);
+
+        let mut leaf = LeafNode::new_for_key_value(&key0, TrieValue::ZERO);
+        leaf.set(key1.last(), TrieValue::from(1));
+        leaf.set(key_max.last(), TrieValue::MAX);
+
+        assert_eq!(leaf.remove(key1.last()), Some(TrieValue::from(1)));
+        assert_eq!(leaf.remove(key_max.last()), Some(TrieValue::MAX));
+        assert_eq!(leaf.remove(key_max.last()), None);
+        assert!(!leaf.is_empty());
+
+        assert_eq!(
+            fr_to_b256(&leaf.commitment_hash()).to_string(),
+            "0x1c0727f0c6c9887189f75a9d08b804aba20892a238e147750767eac22a830d08"
+        );
+
+        leaf.remove(key0.last());
+        assert!(leaf.is_empty());
+        assert_eq!(leaf.commitment(), LeafNode::new(key0.stem()).commitment());
+    }
 }
@@ -1,4 +1,9 @@
-pub use self::{branch::BranchNode, commitment::CommitmentNode, leaf::LeafNode, node::Node};
+pub use self::{
+    branch::BranchNode,
+    commitment::CommitmentNode,
+    leaf::LeafNode,
+    node::{Node, NodeTrait},
+};
 
 mod branch;
 mod commitment;
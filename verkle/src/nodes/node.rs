@@ -5,7 +5,7 @@ use anyhow::{anyhow, bail, Result};
 use banderwagon::{Element, Fr};
 use ssz::{Decode, Encode};
 
-use crate::{Db, TrieKey, TrieValue};
+use crate::{iterator::StemEntry, Db, TrieKey, TrieValue, WriteBatch};
 
 use super::{BranchNode, CommitmentNode, LeafNode};
 
@@ -99,13 +99,7 @@ impl Node {
                     }
                 }
                 Node::Commitment(commitment_node) => {
-                    let Some(bytes) = db.read(&commitment_node.commitment())? else {
-                        bail!("Node {:?} not found in db", commitment_node.commitment())
-                    };
-                    let new_node = Node::from_ssz_bytes(&bytes)
-                        .map_err(|e| anyhow!("Error decoding node: {e:?}"))?;
-                    new_node.check(&commitment_node.commitment())?;
-                    *node = new_node;
+                    *node = Self::load(commitment_node, db)?;
                 }
             };
         }
@@ -132,34 +126,136 @@ impl Node {
                 }
             }
             Node::Commitment(commitment_node) => {
-                let Some(bytes) = db.read(&commitment_node.commitment())? else {
-                    bail!("Node {:?} not found in db", commitment_node.commitment())
-                };
-                let mut node = Node::from_ssz_bytes(&bytes)
-                    .map_err(|e| anyhow!("Error decoding node: {e:?}"))?;
+                let mut node = Self::load(commitment_node, db)?;
                 node.insert(depth, key, value, db)?;
-                node.check(&commitment_node.commitment())?;
                 *self = node;
             }
         };
         Ok(())
     }
 
-    pub fn write_and_commit(&mut self, db: &mut Db) -> Result<Element> {
+    /// Inserts values of all stems at once. See [BranchNode::insert_stems].
+    pub(crate) fn insert_stems(
+        &mut self,
+        depth: usize,
+        stems: Vec<StemEntry>,
+        db: &Db,
+    ) -> Result<()> {
+        if stems.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Node::Branch(branch_node) => branch_node.insert_stems(depth, stems, db)?,
+            Node::Leaf(leaf_node) => {
+                if let [(stem, _)] = stems.as_slice() {
+                    if stem == leaf_node.stem() {
+                        let (_, values) = stems.into_iter().next().expect("Single stem");
+                        leaf_node.set_all(values);
+                        return Ok(());
+                    }
+                }
+
+                let mut branch_node = BranchNode::new();
+                branch_node.set(
+                    leaf_node.stem()[depth],
+                    Node::Leaf(mem::replace(
+                        leaf_node,
+                        LeafNode::new(TrieKey(B256::ZERO).stem()),
+                    )),
+                );
+                branch_node.insert_stems(depth, stems, db)?;
+
+                *self = Node::Branch(branch_node)
+            }
+            Node::Commitment(commitment_node) => {
+                let mut node = Self::load(commitment_node, db)?;
+                node.insert_stems(depth, stems, db)?;
+                *self = node;
+            }
+        };
+        Ok(())
+    }
+
+    /// Removes the value for the given key. Leaves and branches that become empty are removed
+    /// from their parent, but (following go-verkle) remaining nodes are not collapsed upwards.
+    pub fn remove(&mut self, depth: usize, key: TrieKey, db: &Db) -> Result<()> {
+        match self {
+            Node::Branch(branch_node) => branch_node.remove(depth, key, db)?,
+            Node::Leaf(leaf_node) => {
+                if leaf_node.stem() == &key.stem() {
+                    leaf_node.remove(key.last());
+                }
+            }
+            Node::Commitment(commitment_node) => {
+                let mut node = Self::load(commitment_node, db)?;
+                node.remove(depth, key, db)?;
+                *self = node;
+            }
+        };
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Node::Branch(branch_node) => branch_node.is_empty(),
+            Node::Leaf(leaf_node) => leaf_node.is_empty(),
+            Node::Commitment(_) => false,
+        }
+    }
+
+    /// Replaces the Commitment node with the node loaded from the db.
+    pub(crate) fn resolve(&mut self, db: &Db) -> Result<()> {
+        if let Node::Commitment(commitment_node) = self {
+            *self = Self::load(commitment_node, db)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load(commitment_node: &CommitmentNode, db: &Db) -> Result<Self> {
+        let Some(bytes) = db.read(&commitment_node.commitment())? else {
+            bail!("Node {:?} not found in db", commitment_node.commitment())
+        };
+        let node =
+            Node::from_ssz_bytes(&bytes).map_err(|e| anyhow!("Error decoding node: {e:?}"))?;
+        node.check(&commitment_node.commitment())?;
+        Ok(node)
+    }
+
+    /// Adds the node and all its modified descendants to the batch.
+    ///
+    /// Nodes are kept in memory, so the caller should replace the node with the Commitment node
+    /// once the batch is written to the Db.
+    pub fn write_and_commit(&mut self, batch: &mut WriteBatch) -> Element {
+        match self {
+            Node::Branch(branch_node) => {
+                let c = branch_node.write_and_commit(batch);
+                batch.write(c, self.as_ssz_bytes());
+                c
+            }
+            Node::Leaf(leaf_node) => {
+                let c = leaf_node.commitment_write();
+                batch.write(c, self.as_ssz_bytes());
+                c
+            }
+            Node::Commitment(commitment_node) => commitment_node.commitment_write(),
+        }
+    }
+
+    /// Same as [Node::write_and_commit], but independent subtrees are committed in parallel.
+    #[cfg(feature = "rayon")]
+    pub fn par_write_and_commit(&mut self, batch: &mut WriteBatch) -> Element {
         match self {
             Node::Branch(branch_node) => {
-                let c = branch_node.write_and_commit(db)?;
-                db.write(c, self.as_ssz_bytes())?;
-                *self = Node::Commitment(CommitmentNode::new(c));
-                Ok(c)
+                let c = branch_node.par_write_and_commit(batch);
+                batch.write(c, self.as_ssz_bytes());
+                c
             }
             Node::Leaf(leaf_node) => {
                 let c = leaf_node.commitment_write();
-                db.write(c, self.as_ssz_bytes())?;
-                *self = Node::Commitment(CommitmentNode::new(c));
-                Ok(c)
+                batch.write(c, self.as_ssz_bytes());
+                c
             }
-            Node::Commitment(commitment_node) => Ok(commitment_node.commitment_write()),
+            Node::Commitment(commitment_node) => commitment_node.commitment_write(),
         }
     }
 }
@@ -0,0 +1,624 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use alloy_primitives::B256;
+use anyhow::{anyhow, bail, ensure, Result};
+use banderwagon::{CanonicalDeserialize, Element, Fr, One, PrimeField, Zero};
+use ipa_multipoint::{
+    lagrange_basis::{LagrangeBasis, PrecomputedWeights},
+    multiproof::{MultiPoint, MultiPointProof, ProverQuery, VerifierQuery},
+    transcript::Transcript,
+};
+use once_cell::sync::Lazy;
+
+use crate::{
+    constants::VERKLE_NODE_WIDTH,
+    crs::IPA_CRS,
+    nodes::{LeafNode, Node, NodeTrait},
+    stem::Stem,
+    utils::{b256_to_element, element_to_b256, fr_to_b256},
+    Db, TrieKey, TrieValue,
+};
+
+const IPA_PROOF_DEPTH: usize = 8;
+const TRANSCRIPT_LABEL: &[u8] = b"vt";
+
+static PRECOMPUTED_WEIGHTS: Lazy<PrecomputedWeights> =
+    Lazy::new(|| PrecomputedWeights::new(VERKLE_NODE_WIDTH));
+
+/// The status of the stem in the trie, encoded in the lowest 3 bits of `depth_extension_present`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtensionStatus {
+    /// There is no node at the path of the stem.
+    Absent = 0,
+    /// There is leaf node with different stem at the path of the stem.
+    Other = 1,
+    /// There is leaf node with the stem.
+    Present = 2,
+}
+
+impl ExtensionStatus {
+    fn encode(self, depth: usize) -> u8 {
+        ((depth as u8) << 3) | self as u8
+    }
+
+    fn decode(byte: u8) -> Result<(usize, Self)> {
+        let status = match byte & 0b111 {
+            0 => Self::Absent,
+            1 => Self::Other,
+            2 => Self::Present,
+            status => bail!("Invalid extension status: {status}"),
+        };
+        Ok(((byte >> 3) as usize, status))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpaProof {
+    pub cl: Vec<Element>,
+    pub cr: Vec<Element>,
+    pub final_evaluation: Fr,
+}
+
+/// The proof of the values (or their absence) for multiple keys, as specified by EIP-6800.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerkleProof {
+    pub other_stems: Vec<Stem>,
+    pub depth_extension_present: Vec<u8>,
+    pub commitments_by_path: Vec<Element>,
+    pub d: Element,
+    pub ipa_proof: IpaProof,
+}
+
+impl VerkleProof {
+    /// Creates the proof for the given keys. All nodes in the trie should be committed.
+    pub(crate) fn create(root: &mut Node, keys: &[TrieKey], db: &Db) -> Result<Self> {
+        let mut stems = BTreeMap::<Stem, BTreeSet<u8>>::new();
+        for key in keys {
+            stems.entry(key.stem()).or_default().insert(key.last());
+        }
+        ensure!(!stems.is_empty(), "Can't create proof without keys");
+        let stems = stems
+            .into_iter()
+            .map(|(stem, suffixes)| (stem, suffixes.into_iter().collect()))
+            .collect::<Vec<_>>();
+
+        let mut prover = Prover::default();
+        prover.prove_node(root, 0, &stems, db)?;
+
+        let multipoint_proof = MultiPoint::open(
+            IPA_CRS.clone(),
+            &PRECOMPUTED_WEIGHTS,
+            &mut Transcript::new(TRANSCRIPT_LABEL),
+            prover.queries,
+        );
+        let (d, ipa_proof) = Self::split_multipoint_proof(&multipoint_proof)?;
+
+        Ok(Self {
+            other_stems: prover.other_stems,
+            depth_extension_present: prover.depth_extension_present,
+            commitments_by_path: prover.commitments_by_path,
+            d,
+            ipa_proof,
+        })
+    }
+
+    /// Verifies that the proof proves given values (`None` representing absent value) against
+    /// the root.
+    pub fn verify(&self, root: B256, key_values: &[(TrieKey, Option<TrieValue>)]) -> Result<()> {
+        let mut stems = BTreeMap::<Stem, BTreeMap<u8, Option<TrieValue>>>::new();
+        for (key, value) in key_values {
+            let previous = stems
+                .entry(key.stem())
+                .or_default()
+                .insert(key.last(), *value);
+            if previous.is_some_and(|previous| previous != *value) {
+                bail!("Different values provided for the same key");
+            }
+        }
+        ensure!(!stems.is_empty(), "Can't verify proof without keys");
+        ensure!(
+            stems.len() == self.depth_extension_present.len(),
+            "Invalid depth_extension_present length: expected {} but was {}",
+            stems.len(),
+            self.depth_extension_present.len()
+        );
+
+        let stems = stems
+            .into_iter()
+            .zip(&self.depth_extension_present)
+            .map(|((stem, values), byte)| {
+                let (depth, status) = ExtensionStatus::decode(*byte)?;
+                ensure!(
+                    (1..=Stem::STEM_LENGTH).contains(&depth),
+                    "Invalid depth: {depth}"
+                );
+                Ok(StemInfo {
+                    stem,
+                    depth,
+                    status,
+                    values: values.into_iter().collect(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut verifier = Verifier {
+            commitments: self.commitments_by_path.iter(),
+            other_stems: self.other_stems.iter(),
+            queries: vec![],
+        };
+        verifier.verify_node(b256_to_element(&root), 0, &stems)?;
+        ensure!(
+            verifier.commitments.next().is_none(),
+            "Unused commitments in the proof"
+        );
+        ensure!(
+            verifier.other_stems.next().is_none(),
+            "Unused other stems in the proof"
+        );
+
+        let multipoint_proof = self.multipoint_proof()?;
+        if multipoint_proof.check(
+            &IPA_CRS,
+            &PRECOMPUTED_WEIGHTS,
+            &verifier.queries,
+            &mut Transcript::new(TRANSCRIPT_LABEL),
+        ) {
+            Ok(())
+        } else {
+            bail!("Invalid multiproof")
+        }
+    }
+
+    /// Splits the serialized multipoint proof into `d` and IPA proof.
+    ///
+    /// The serialized format is: `d || cl || cr || final_evaluation`.
+    fn split_multipoint_proof(multipoint_proof: &MultiPointProof) -> Result<(Element, IpaProof)> {
+        let bytes = multipoint_proof.to_bytes()?;
+        let mut chunks = bytes.chunks_exact(B256::len_bytes());
+        let mut next_element = || {
+            chunks
+                .next()
+                .map(|chunk| b256_to_element(&B256::from_slice(chunk)))
+                .ok_or(anyhow!("Multipoint proof too short"))
+        };
+
+        let d = next_element()?;
+        let cl = (0..IPA_PROOF_DEPTH)
+            .map(|_| next_element())
+            .collect::<Result<_>>()?;
+        let cr = (0..IPA_PROOF_DEPTH)
+            .map(|_| next_element())
+            .collect::<Result<_>>()?;
+        let final_evaluation = Fr::deserialize_compressed(
+            chunks.next().ok_or(anyhow!("Multipoint proof too short"))?,
+        )?;
+        ensure!(chunks.next().is_none(), "Multipoint proof too long");
+
+        Ok((
+            d,
+            IpaProof {
+                cl,
+                cr,
+                final_evaluation,
+            },
+        ))
+    }
+
+    fn multipoint_proof(&self) -> Result<MultiPointProof> {
+        ensure!(
+            self.ipa_proof.cl.len() == IPA_PROOF_DEPTH
+                && self.ipa_proof.cr.len() == IPA_PROOF_DEPTH,
+            "Invalid IPA proof depth"
+        );
+
+        let mut bytes = vec![];
+        bytes.extend(element_to_b256(&self.d));
+        for element in self.ipa_proof.cl.iter().chain(&self.ipa_proof.cr) {
+            bytes.extend(element_to_b256(element));
+        }
+        bytes.extend(fr_to_b256(&self.ipa_proof.final_evaluation));
+
+        Ok(MultiPointProof::from_bytes(&bytes, VERKLE_NODE_WIDTH)?)
+    }
+}
+
+/// Collects openings while traversing the trie.
+///
+/// Stems are processed in sorted order and grouped by their common path, so every node is
+/// visited (and every opening is created) exactly once. The [Verifier] recreates openings in the
+/// same order.
+#[derive(Default)]
+struct Prover {
+    other_stems: Vec<Stem>,
+    depth_extension_present: Vec<u8>,
+    commitments_by_path: Vec<Element>,
+    queries: Vec<ProverQuery>,
+}
+
+impl Prover {
+    fn open(&mut self, commitment: Element, evaluations: &[Fr], point: usize) {
+        self.queries.push(ProverQuery {
+            commitment,
+            poly: LagrangeBasis::new(evaluations.to_vec()),
+            point,
+            result: evaluations[point],
+        });
+    }
+
+    /// Proves stems (sorted, with sorted suffixes) that share path to the node.
+    fn prove_node(
+        &mut self,
+        node: &mut Node,
+        depth: usize,
+        stems: &[(Stem, Vec<u8>)],
+        db: &Db,
+    ) -> Result<()> {
+        node.resolve(db)?;
+        if depth > 0 {
+            self.commitments_by_path.push(node.commitment());
+        }
+
+        match node {
+            Node::Branch(branch_node) => {
+                let commitment = branch_node.commitment();
+                let evaluations = branch_node.evaluations();
+                for group in stems.chunk_by(|(a, _), (b, _)| a[depth] == b[depth]) {
+                    let index = group[0].0[depth];
+                    self.open(commitment, &evaluations, index as usize);
+                    match branch_node.get_mut(index) {
+                        Some(child) => self.prove_node(child, depth + 1, group, db)?,
+                        None => self.depth_extension_present.extend(
+                            group
+                                .iter()
+                                .map(|_| ExtensionStatus::Absent.encode(depth + 1)),
+                        ),
+                    }
+                }
+            }
+            Node::Leaf(leaf_node) => self.prove_leaf(leaf_node, depth, stems),
+            Node::Commitment(_) => bail!("Commitment node should be resolved"),
+        }
+        Ok(())
+    }
+
+    fn prove_leaf(&mut self, leaf_node: &LeafNode, depth: usize, stems: &[(Stem, Vec<u8>)]) {
+        let commitment = leaf_node.commitment();
+        let evaluations = leaf_node.evaluations();
+        self.open(commitment, &evaluations, 0);
+        self.open(commitment, &evaluations, 1);
+
+        let mut present = false;
+        for (stem, suffixes) in stems {
+            if stem != leaf_node.stem() {
+                self.depth_extension_present
+                    .push(ExtensionStatus::Other.encode(depth));
+                continue;
+            }
+            present = true;
+            self.depth_extension_present
+                .push(ExtensionStatus::Present.encode(depth));
+
+            let half = VERKLE_NODE_WIDTH / 2;
+            for half_suffixes in suffixes.chunk_by(|a, b| *a as usize / half == *b as usize / half)
+            {
+                let suffix = half_suffixes[0];
+                self.open(commitment, &evaluations, 2 + suffix as usize / half);
+
+                let suffix_commitment = leaf_node.suffix_commitment(suffix);
+                let suffix_evaluations = leaf_node.suffix_evaluations(suffix);
+                self.commitments_by_path.push(suffix_commitment);
+                for suffix in half_suffixes {
+                    let low_index = *suffix as usize % half * 2;
+                    self.open(suffix_commitment, &suffix_evaluations, low_index);
+                    self.open(suffix_commitment, &suffix_evaluations, low_index + 1);
+                }
+            }
+        }
+        if !present {
+            self.other_stems.push(*leaf_node.stem());
+        }
+    }
+}
+
+struct StemInfo {
+    stem: Stem,
+    depth: usize,
+    status: ExtensionStatus,
+    values: Vec<(u8, Option<TrieValue>)>,
+}
+
+/// Recreates openings from the proof, in the same order as [Prover].
+struct Verifier<'a> {
+    commitments: std::slice::Iter<'a, Element>,
+    other_stems: std::slice::Iter<'a, Stem>,
+    queries: Vec<VerifierQuery>,
+}
+
+impl Verifier<'_> {
+    fn open(&mut self, commitment: Element, point: usize, result: Fr) {
+        self.queries.push(VerifierQuery {
+            commitment,
+            point: Fr::from(point as u64),
+            result,
+        });
+    }
+
+    fn next_commitment(&mut self) -> Result<Element> {
+        self.commitments
+            .next()
+            .copied()
+            .ok_or(anyhow!("Missing commitments in the proof"))
+    }
+
+    fn verify_node(&mut self, commitment: Element, depth: usize, stems: &[StemInfo]) -> Result<()> {
+        for group in stems.chunk_by(|a, b| a.stem[depth] == b.stem[depth]) {
+            let index = group[0].stem[depth] as usize;
+            if group.iter().all(|stem_info| stem_info.depth > depth + 1) {
+                let child_commitment = self.next_commitment()?;
+                self.open(commitment, index, child_commitment.map_to_scalar_field());
+                self.verify_node(child_commitment, depth + 1, group)?;
+            } else if group.iter().all(|stem_info| stem_info.depth == depth + 1) {
+                if group
+                    .iter()
+                    .all(|stem_info| stem_info.status == ExtensionStatus::Absent)
+                {
+                    self.open(commitment, index, Fr::zero());
+                } else {
+                    let child_commitment = self.next_commitment()?;
+                    self.open(commitment, index, child_commitment.map_to_scalar_field());
+                    self.verify_leaf(child_commitment, depth + 1, group)?;
+                }
+            } else {
+                bail!("Inconsistent depths of stems with common path");
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_leaf(&mut self, commitment: Element, depth: usize, stems: &[StemInfo]) -> Result<()> {
+        let mut present_stems = stems
+            .iter()
+            .filter(|stem_info| stem_info.status == ExtensionStatus::Present);
+        let leaf_stem = match present_stems.next() {
+            Some(stem_info) => stem_info.stem,
+            None => {
+                let other_stem = *self
+                    .other_stems
+                    .next()
+                    .ok_or(anyhow!("Missing other stems in the proof"))?;
+                ensure!(
+                    other_stem[..depth] == stems[0].stem[..depth],
+                    "Other stem doesn't share the path with proven stems"
+                );
+                other_stem
+            }
+        };
+        ensure!(
+            present_stems.next().is_none(),
+            "Multiple present stems at the same path"
+        );
+
+        self.open(commitment, 0, Fr::one());
+        self.open(
+            commitment,
+            1,
+            Fr::from_le_bytes_mod_order(leaf_stem.as_slice()),
+        );
+
+        for stem_info in stems {
+            match stem_info.status {
+                ExtensionStatus::Absent => bail!("Absent stem at the path of the leaf"),
+                ExtensionStatus::Other => {
+                    ensure!(
+                        stem_info.stem != leaf_stem,
+                        "Other stem matches proven stem"
+                    );
+                    continue;
+                }
+                ExtensionStatus::Present => {}
+            }
+
+            let half = VERKLE_NODE_WIDTH / 2;
+            for half_values in stem_info
+                .values
+                .chunk_by(|(a, _), (b, _)| *a as usize / half == *b as usize / half)
+            {
+                let suffix_commitment = self.next_commitment()?;
+                self.open(
+                    commitment,
+                    2 + half_values[0].0 as usize / half,
+                    suffix_commitment.map_to_scalar_field(),
+                );
+                for (suffix, value) in half_values {
+                    let low_index = *suffix as usize % half * 2;
+                    let (value_low_16, value_high_16) = LeafNode::value_evaluations(value.as_ref());
+                    self.open(suffix_commitment, low_index, value_low_16);
+                    self.open(suffix_commitment, low_index + 1, value_high_16);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+    use anyhow::Result;
+    use ark_ff::UniformRand;
+    use claims::{assert_err, assert_ok};
+    use db::memory_db::MemoryDb;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::Trie;
+
+    use super::*;
+
+    fn init() -> Trie {
+        Trie::new(Box::new(MemoryDb::new()))
+    }
+
+    fn key_with_last_byte(key: TrieKey, last_byte: u8) -> TrieKey {
+        TrieKey::from_stem_and_last_byte(&key.stem(), last_byte)
+    }
+
+    #[test]
+    fn empty_trie() -> Result<()> {
+        let mut trie = init();
+        let key = TrieKey::new(B256::ZERO);
+
+        let proof = trie.prove(&[key])?;
+        assert_eq!(
+            proof.depth_extension_present,
+            vec![ExtensionStatus::Absent.encode(1)]
+        );
+        assert!(proof.commitments_by_path.is_empty());
+        assert_ok!(proof.verify(trie.root()?, &[(key, None)]));
+        assert_err!(proof.verify(trie.root()?, &[(key, Some(TrieValue::ZERO))]));
+        Ok(())
+    }
+
+    #[test]
+    fn present_keys() -> Result<()> {
+        let mut trie = init();
+        let key0 = TrieKey::new(B256::ZERO);
+        let key1 = TrieKey::new(U256::from(1).into());
+        let key_max = TrieKey::new(U256::MAX.into());
+        trie.insert(key0, TrieValue::ZERO)?;
+        trie.insert(key1, TrieValue::from(1))?;
+        trie.insert(key_max, TrieValue::MAX)?;
+        let root = trie.root()?;
+
+        let proof = trie.prove(&[key0, key1, key_max])?;
+        assert_eq!(
+            proof.depth_extension_present,
+            vec![
+                ExtensionStatus::Present.encode(1),
+                ExtensionStatus::Present.encode(1),
+            ]
+        );
+        assert_ok!(proof.verify(
+            root,
+            &[
+                (key0, Some(TrieValue::ZERO)),
+                (key1, Some(TrieValue::from(1))),
+                (key_max, Some(TrieValue::MAX)),
+            ]
+        ));
+
+        // Wrong value
+        assert_err!(proof.verify(
+            root,
+            &[
+                (key0, Some(TrieValue::ZERO)),
+                (key1, Some(TrieValue::from(2))),
+                (key_max, Some(TrieValue::MAX)),
+            ]
+        ));
+        // Claimed absence
+        assert_err!(proof.verify(
+            root,
+            &[
+                (key0, Some(TrieValue::ZERO)),
+                (key1, None),
+                (key_max, Some(TrieValue::MAX)),
+            ]
+        ));
+        // Wrong root
+        assert_err!(proof.verify(
+            element_to_b256(&Element::zero()),
+            &[
+                (key0, Some(TrieValue::ZERO)),
+                (key1, Some(TrieValue::from(1))),
+                (key_max, Some(TrieValue::MAX)),
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn absent_keys() -> Result<()> {
+        let mut trie = init();
+        let key0 = TrieKey::new(B256::ZERO);
+        trie.insert(key0, TrieValue::ZERO)?;
+        let root = trie.root()?;
+
+        // Same stem, other stem (at the same path) and empty path
+        let same_stem = key_with_last_byte(key0, 200);
+        let other_stem = TrieKey::new(U256::from(1u64 << 16).into());
+        let empty_path = TrieKey::new(U256::MAX.into());
+
+        let proof = trie.prove(&[same_stem, other_stem, empty_path])?;
+        assert_eq!(proof.other_stems, vec![]);
+        assert_eq!(
+            proof.depth_extension_present,
+            vec![
+                ExtensionStatus::Present.encode(1),
+                ExtensionStatus::Other.encode(1),
+                ExtensionStatus::Absent.encode(1),
+            ]
+        );
+        assert_ok!(proof.verify(
+            root,
+            &[(same_stem, None), (other_stem, None), (empty_path, None)]
+        ));
+        assert_err!(proof.verify(
+            root,
+            &[
+                (same_stem, Some(TrieValue::ZERO)),
+                (other_stem, None),
+                (empty_path, None)
+            ]
+        ));
+
+        let proof = trie.prove(&[other_stem])?;
+        assert_eq!(proof.other_stems, vec![key0.stem()]);
+        assert_ok!(proof.verify(root, &[(other_stem, None)]));
+        Ok(())
+    }
+
+    #[test]
+    fn random() -> Result<()> {
+        let mut trie = init();
+        let mut rng = StdRng::seed_from_u64(12345);
+
+        let key_values = (0..1000)
+            .map(|_| {
+                (
+                    TrieKey::new(B256::random_with(&mut rng)),
+                    U256::rand(&mut rng),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (key, value) in &key_values {
+            trie.insert(*key, *value)?;
+        }
+        let root = trie.root()?;
+
+        let mut proven_key_values = key_values
+            .iter()
+            .step_by(10)
+            .map(|(key, value)| (*key, Some(*value)))
+            .collect::<Vec<_>>();
+        proven_key_values.extend(
+            key_values
+                .iter()
+                .step_by(10)
+                .map(|(key, _)| (key_with_last_byte(*key, key.last().wrapping_add(1)), None)),
+        );
+        proven_key_values
+            .extend((0..100).map(|_| (TrieKey::new(B256::random_with(&mut rng)), None)));
+
+        let keys = proven_key_values
+            .iter()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let proof = trie.prove(&keys)?;
+        assert_ok!(proof.verify(root, &proven_key_values));
+
+        let mut tampered_proof = proof.clone();
+        tampered_proof.commitments_by_path.swap(0, 1);
+        assert_err!(tampered_proof.verify(root, &proven_key_values));
+        Ok(())
+    }
+}
@@ -3,11 +3,11 @@ use ssz::{Decode, Encode};
 
 use crate::TrieKey;
 
-#[derive(PartialEq, Eq, AsRef, Deref, Index)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, AsRef, Deref, Index)]
 pub struct Stem([u8; Self::STEM_LENGTH]);
 
 impl Stem {
-    const STEM_LENGTH: usize = 31;
+    pub(crate) const STEM_LENGTH: usize = 31;
 }
 
 impl From<&TrieKey> for Stem {
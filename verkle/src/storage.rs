@@ -1,12 +1,22 @@
-use alloy_primitives::{Address, B256, U256};
-use banderwagon::{Fr, PrimeField};
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use anyhow::{ensure, Result};
+use banderwagon::{Element, Fr, PrimeField};
+use lru::LruCache;
+use once_cell::sync::{Lazy, OnceCell};
+use sha2::{Digest, Sha256};
 
 use crate::{
     committer::DEFAULT_COMMITER,
     constants::{
-        BALANCE_LEAF_KEY, CODE_KECCAK_LEAF_KEY, CODE_OFFSET, CODE_SIZE_LEAF_KEY,
-        HEADER_STORAGE_OFFSET, MAIN_STORAGE_OFFSET, NONCE_LEAF_KEY, VERKLE_NODE_WIDTH_U256,
-        VERSION_LEAF_KEY,
+        BALANCE_LEAF_KEY, BASIC_DATA_BALANCE_OFFSET, BASIC_DATA_CODE_SIZE_OFFSET,
+        BASIC_DATA_LEAF_KEY, BASIC_DATA_NONCE_OFFSET, CODE_HASH_LEAF_KEY, CODE_KECCAK_LEAF_KEY,
+        CODE_OFFSET, CODE_SIZE_LEAF_KEY, HEADER_STORAGE_OFFSET, MAIN_STORAGE_OFFSET,
+        NONCE_LEAF_KEY, VERKLE_NODE_WIDTH_U256, VERSION_LEAF_KEY,
     },
     stem::Stem,
     utils::fr_to_b256,
@@ -15,38 +25,316 @@ use crate::{
 
 type Address32 = B256;
 
+/// Derives the key of the `sub_index` value of the `tree_index` stem of the account storage.
+///
+/// Hashers that derive keys from the commitment to the address can store it in
+/// `address_commitment`, which is kept by [AccountStorageLayout] and shared by all keys of the
+/// account.
+pub trait TreeKeyHasher: Send + Sync {
+    fn tree_key(
+        &self,
+        address: &Address32,
+        address_commitment: &OnceCell<Element>,
+        tree_index: U256,
+        sub_index: u8,
+    ) -> TrieKey;
+}
+
+pub const DEFAULT_KEY_CACHE_CAPACITY: usize = 1 << 16;
+
+/// The Pedersen hasher with the default cache, shared by all layouts that don't set the hasher.
+pub static DEFAULT_KEY_HASHER: Lazy<Arc<PedersenKeyHasher>> =
+    Lazy::new(|| Arc::new(PedersenKeyHasher::new(DEFAULT_KEY_CACHE_CAPACITY)));
+
+/// Keys are derived from the Pedersen commitment of the address and tree index (EIP-6800).
+///
+/// Recently derived stems are cached. The commitment is linear, so the part that depends only on
+/// the address is computed once by [AccountStorageLayout] and shared by all stems of the account.
+pub struct PedersenKeyHasher {
+    stems: Option<Mutex<LruCache<(Address32, U256), Stem>>>,
+}
+
+impl PedersenKeyHasher {
+    /// Creates the hasher that caches up to `cache_capacity` stems. Zero capacity disables
+    /// caching.
+    pub fn new(cache_capacity: usize) -> Self {
+        Self {
+            stems: NonZeroUsize::new(cache_capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    fn lock_stems(&self) -> Option<MutexGuard<'_, LruCache<(Address32, U256), Stem>>> {
+        self.stems
+            .as_ref()
+            .map(|stems| stems.lock().expect("Key cache lock shouldn't be poisoned"))
+    }
+
+    fn commit_address(address: &Address32) -> Element {
+        DEFAULT_COMMITER.commit_sparse(vec![
+            (0, Fr::from(2u128 + 256 * 64)),
+            (1, Fr::from_le_bytes_mod_order(&address[..16])),
+            (2, Fr::from_le_bytes_mod_order(&address[16..])),
+        ])
+    }
+
+    fn stem(mut address_commitment: Element, tree_index: U256) -> Stem {
+        let tree_index_bytes = tree_index.to_le_bytes::<32>();
+        address_commitment += DEFAULT_COMMITER.commit_sparse(vec![
+            (3, Fr::from_le_bytes_mod_order(&tree_index_bytes[..16])),
+            (4, Fr::from_le_bytes_mod_order(&tree_index_bytes[16..])),
+        ]);
+        let hash_commitment = address_commitment.map_to_scalar_field();
+        TrieKey::new(fr_to_b256(&hash_commitment)).stem()
+    }
+}
+
+impl Default for PedersenKeyHasher {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEY_CACHE_CAPACITY)
+    }
+}
+
+impl TreeKeyHasher for PedersenKeyHasher {
+    fn tree_key(
+        &self,
+        address: &Address32,
+        address_commitment: &OnceCell<Element>,
+        tree_index: U256,
+        sub_index: u8,
+    ) -> TrieKey {
+        let cache_key = (*address, tree_index);
+        if let Some(stem) = self
+            .lock_stems()
+            .and_then(|mut stems| stems.get(&cache_key).copied())
+        {
+            return TrieKey::from_stem_and_last_byte(&stem, sub_index);
+        }
+
+        // The cache isn't locked while the stem is computed, so other threads can use it
+        let address_commitment = *address_commitment.get_or_init(|| Self::commit_address(address));
+        let stem = Self::stem(address_commitment, tree_index);
+        if let Some(mut stems) = self.lock_stems() {
+            stems.put(cache_key, stem);
+        }
+        TrieKey::from_stem_and_last_byte(&stem, sub_index)
+    }
+}
+
+/// Keys are derived from the SHA-256 hash of the address and tree index (EIP-7864).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256KeyHasher;
+
+impl TreeKeyHasher for Sha256KeyHasher {
+    fn tree_key(
+        &self,
+        address: &Address32,
+        _address_commitment: &OnceCell<Element>,
+        tree_index: U256,
+        sub_index: u8,
+    ) -> TrieKey {
+        let mut hasher = Sha256::new();
+        hasher.update(address);
+        hasher.update(tree_index.to_le_bytes::<32>());
+
+        let mut key = B256::from_slice(&hasher.finalize());
+        key[31] = sub_index;
+        key.into()
+    }
+}
+
+/// The layout of the account header leaves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderLayout {
+    /// Version, balance, nonce, code hash and code size are stored in separate leaves (devnet6).
+    #[default]
+    Separate,
+    /// Version, code size, nonce and balance are packed into the basic data leaf, followed by the
+    /// code hash leaf (EIP-6800).
+    BasicData,
+}
+
+/// The content of the basic data leaf, see [HeaderLayout::BasicData].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BasicData {
+    pub version: u8,
+    pub code_size: u32,
+    pub nonce: u64,
+    pub balance: u128,
+}
+
+impl BasicData {
+    /// Code size is stored in 3 bytes.
+    pub const MAX_CODE_SIZE: usize = (1 << 24) - 1;
+
+    pub fn new(balance: U256, nonce: u64, code_size: usize) -> Result<Self> {
+        Self::check_code_size(code_size)?;
+        Ok(Self {
+            version: 0,
+            code_size: code_size as u32,
+            nonce,
+            balance: u128::try_from(balance)?,
+        })
+    }
+
+    pub fn check_code_size(code_size: usize) -> Result<()> {
+        ensure!(
+            code_size <= Self::MAX_CODE_SIZE,
+            "Code size doesn't fit 3 bytes: {code_size}"
+        );
+        Ok(())
+    }
+
+    /// Returns the big-endian packed fields, stored as value bytes.
+    pub fn to_value(&self) -> TrieValue {
+        let mut bytes = [0u8; 32];
+        bytes[0] = self.version;
+        bytes[BASIC_DATA_CODE_SIZE_OFFSET..BASIC_DATA_NONCE_OFFSET]
+            .copy_from_slice(&self.code_size.to_be_bytes()[1..]);
+        bytes[BASIC_DATA_NONCE_OFFSET..BASIC_DATA_BALANCE_OFFSET]
+            .copy_from_slice(&self.nonce.to_be_bytes());
+        bytes[BASIC_DATA_BALANCE_OFFSET..].copy_from_slice(&self.balance.to_be_bytes());
+        TrieValue::from_le_bytes(bytes)
+    }
+
+    pub fn from_value(value: TrieValue) -> Self {
+        let bytes = value.to_le_bytes::<32>();
+        let mut code_size = [0u8; 4];
+        code_size[1..]
+            .copy_from_slice(&bytes[BASIC_DATA_CODE_SIZE_OFFSET..BASIC_DATA_NONCE_OFFSET]);
+        Self {
+            version: bytes[0],
+            code_size: u32::from_be_bytes(code_size),
+            nonce: u64::from_be_bytes(
+                bytes[BASIC_DATA_NONCE_OFFSET..BASIC_DATA_BALANCE_OFFSET]
+                    .try_into()
+                    .expect("Nonce has 8 bytes"),
+            ),
+            balance: u128::from_be_bytes(
+                bytes[BASIC_DATA_BALANCE_OFFSET..]
+                    .try_into()
+                    .expect("Balance has 16 bytes"),
+            ),
+        }
+    }
+}
+
 pub struct AccountStorageLayout {
     address32: Address32,
+    address_commitment: OnceCell<Element>,
     base_storage_stem: Stem,
+    header_layout: HeaderLayout,
+    key_hasher: Arc<dyn TreeKeyHasher>,
 }
 
 impl AccountStorageLayout {
     pub fn new(address: Address) -> Self {
+        Self::new_with_layout(address, HeaderLayout::default())
+    }
+
+    pub fn new_with_layout(address: Address, header_layout: HeaderLayout) -> Self {
+        Self::new_with_key_hasher(address, header_layout, DEFAULT_KEY_HASHER.clone())
+    }
+
+    pub fn new_with_key_hasher(
+        address: Address,
+        header_layout: HeaderLayout,
+        key_hasher: Arc<dyn TreeKeyHasher>,
+    ) -> Self {
         let address32 = Address32::left_padding_from(address.as_slice());
+        let address_commitment = OnceCell::new();
         Self {
             address32,
-            base_storage_stem: tree_key(&address32, U256::ZERO, 0).into(),
+            base_storage_stem: key_hasher
+                .tree_key(&address32, &address_commitment, U256::ZERO, 0)
+                .into(),
+            address_commitment,
+            header_layout,
+            key_hasher,
+        }
+    }
+
+    pub fn header_layout(&self) -> HeaderLayout {
+        self.header_layout
+    }
+
+    /// Returns the header leaves of the new account. Contracts should also store their code
+    /// chunks, see [AccountStorageLayout::chunkify_code].
+    pub fn account_header(
+        &self,
+        balance: U256,
+        nonce: u64,
+        code: Option<&[u8]>,
+    ) -> Result<Vec<(TrieKey, TrieValue)>> {
+        let code_hash = (
+            self.code_hash_key(),
+            TrieValue::from_le_bytes(keccak256(code.unwrap_or_default()).0),
+        );
+        match self.header_layout {
+            HeaderLayout::Separate => {
+                let mut key_values = vec![
+                    (self.version_key(), TrieValue::ZERO),
+                    (self.balance_key(), balance),
+                    (self.nonce_key(), TrieValue::from(nonce)),
+                    code_hash,
+                ];
+                // Code size is not stored for EOAs
+                if let Some(code) = code {
+                    key_values.push((self.code_size_key(), TrieValue::from(code.len())));
+                }
+                Ok(key_values)
+            }
+            HeaderLayout::BasicData => {
+                let code_size = code.map_or(0, <[u8]>::len);
+                Ok(vec![
+                    (
+                        self.basic_data_key(),
+                        BasicData::new(balance, nonce, code_size)?.to_value(),
+                    ),
+                    code_hash,
+                ])
+            }
         }
     }
 
+    /// Only used by [HeaderLayout::BasicData], it has the same key as the version leaf.
+    pub fn basic_data_key(&self) -> TrieKey {
+        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, BASIC_DATA_LEAF_KEY)
+    }
+
+    /// Only used by [HeaderLayout::Separate], same as balance, nonce and code size keys. Panics
+    /// for other layouts, as their leaves can have the same keys.
     pub fn version_key(&self) -> TrieKey {
-        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, VERSION_LEAF_KEY)
+        self.separate_header_key(VERSION_LEAF_KEY)
     }
 
     pub fn balance_key(&self) -> TrieKey {
-        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, BALANCE_LEAF_KEY)
+        self.separate_header_key(BALANCE_LEAF_KEY)
     }
 
     pub fn nonce_key(&self) -> TrieKey {
-        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, NONCE_LEAF_KEY)
+        self.separate_header_key(NONCE_LEAF_KEY)
     }
 
     pub fn code_hash_key(&self) -> TrieKey {
-        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, CODE_KECCAK_LEAF_KEY)
+        let leaf_key = match self.header_layout {
+            HeaderLayout::Separate => CODE_KECCAK_LEAF_KEY,
+            HeaderLayout::BasicData => CODE_HASH_LEAF_KEY,
+        };
+        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, leaf_key)
     }
 
     pub fn code_size_key(&self) -> TrieKey {
-        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, CODE_SIZE_LEAF_KEY)
+        self.separate_header_key(CODE_SIZE_LEAF_KEY)
+    }
+
+    fn separate_header_key(&self, leaf_key: u8) -> TrieKey {
+        assert_eq!(
+            self.header_layout,
+            HeaderLayout::Separate,
+            "Header leaf {leaf_key} is only used by the separate layout"
+        );
+        TrieKey::from_stem_and_last_byte(&self.base_storage_stem, leaf_key)
     }
 
     pub fn storage_slot_key(&self, storage_key: U256) -> TrieKey {
@@ -55,8 +343,9 @@ impl AccountStorageLayout {
         } else {
             MAIN_STORAGE_OFFSET + storage_key
         };
-        tree_key(
+        self.key_hasher.tree_key(
             &self.address32,
+            &self.address_commitment,
             pos / VERKLE_NODE_WIDTH_U256,
             (pos % VERKLE_NODE_WIDTH_U256).byte(0),
         )
@@ -64,8 +353,9 @@ impl AccountStorageLayout {
 
     pub fn code_key(&self, chunk_id: usize) -> TrieKey {
         let pos = CODE_OFFSET + U256::from(chunk_id);
-        tree_key(
+        self.key_hasher.tree_key(
             &self.address32,
+            &self.address_commitment,
             pos / VERKLE_NODE_WIDTH_U256,
             (pos % VERKLE_NODE_WIDTH_U256).byte(0),
         )
@@ -98,20 +388,99 @@ impl AccountStorageLayout {
     }
 }
 
-fn tree_key(address: &Address32, tree_index: U256, sub_index: u8) -> TrieKey {
-    let tree_index_bytes = tree_index.to_le_bytes::<32>();
-
-    let scalars = [
-        Fr::from(2u128 + 256 * 64),
-        Fr::from_le_bytes_mod_order(&address[..16]),
-        Fr::from_le_bytes_mod_order(&address[16..]),
-        Fr::from_le_bytes_mod_order(&tree_index_bytes[..16]),
-        Fr::from_le_bytes_mod_order(&tree_index_bytes[16..]),
-    ];
-    let commitment = DEFAULT_COMMITER.commit_lagrange(&scalars);
-    let hash_commitment = commitment.map_to_scalar_field();
-
-    let mut key = fr_to_b256(&hash_commitment);
-    key[31] = sub_index;
-    key.into()
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use alloy_primitives::{b256, hex};
+
+    use super::*;
+
+    #[test]
+    fn basic_data() -> Result<()> {
+        let basic_data = BasicData::new(U256::from(0x0607), 0x0405, 0x010203)?;
+        let value = basic_data.to_value();
+        assert_eq!(
+            value.to_le_bytes::<32>(),
+            hex!("0000000000010203000000000000040500000000000000000000000000000607")
+        );
+        assert_eq!(BasicData::from_value(value), basic_data);
+
+        assert!(BasicData::new(U256::from(1) << 128, 0, 0).is_err());
+        assert!(BasicData::new(U256::ZERO, 0, BasicData::MAX_CODE_SIZE + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pedersen_cache() {
+        let uncached = PedersenKeyHasher::new(0);
+        // Small capacity, so that stems are evicted
+        let cached = PedersenKeyHasher::new(2);
+        for address in [B256::ZERO, B256::repeat_byte(1), B256::repeat_byte(0xff)] {
+            // Commitment to the address is shared by all stems of the address
+            let address_commitment = OnceCell::new();
+            for tree_index in [U256::ZERO, U256::from(1), U256::MAX] {
+                let tree_index_bytes = tree_index.to_le_bytes::<32>();
+                let commitment = DEFAULT_COMMITER.commit_lagrange(&[
+                    Fr::from(2u128 + 256 * 64),
+                    Fr::from_le_bytes_mod_order(&address[..16]),
+                    Fr::from_le_bytes_mod_order(&address[16..]),
+                    Fr::from_le_bytes_mod_order(&tree_index_bytes[..16]),
+                    Fr::from_le_bytes_mod_order(&tree_index_bytes[16..]),
+                ]);
+                let mut expected_key = fr_to_b256(&commitment.map_to_scalar_field());
+                expected_key[31] = 5;
+
+                assert_eq!(
+                    *uncached.tree_key(&address, &OnceCell::new(), tree_index, 5),
+                    expected_key
+                );
+                assert_eq!(
+                    *cached.tree_key(&address, &address_commitment, tree_index, 5),
+                    expected_key
+                );
+                // Cached stem with different sub index
+                expected_key[31] = 6;
+                assert_eq!(
+                    *cached.tree_key(&address, &address_commitment, tree_index, 6),
+                    expected_key
+                );
+            }
+            assert_eq!(
+                address_commitment.get(),
+                Some(&PedersenKeyHasher::commit_address(&address))
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only used by the separate layout")]
+    fn separate_header_keys() {
+        // Balance leaf of the separate layout has the same key as the code hash leaf
+        AccountStorageLayout::new_with_layout(Address::repeat_byte(1), HeaderLayout::BasicData)
+            .balance_key();
+    }
+
+    #[test]
+    fn sha256_tree_keys() -> Result<()> {
+        let storage = AccountStorageLayout::new_with_key_hasher(
+            Address::from_str("0x3b7c4c2b2b25239e58f8e67509b32edb5bbf293c")?,
+            HeaderLayout::Separate,
+            Arc::new(Sha256KeyHasher),
+        );
+        assert_eq!(
+            *storage.balance_key(),
+            b256!("fa9db068dc8ac4605e76004d0ca2be94baca3ea575bbbe0ca84bf179f9a23d01")
+        );
+        // Header storage slots share the stem with the account header
+        assert_eq!(
+            *storage.storage_slot_key(U256::from(5)),
+            b256!("fa9db068dc8ac4605e76004d0ca2be94baca3ea575bbbe0ca84bf179f9a23d45")
+        );
+        assert_eq!(
+            *storage.storage_slot_key(U256::from(1000)),
+            b256!("0687f86684d0762254b58321e33907765c73d7f8f24a2edda7157b340abf32e8")
+        );
+        Ok(())
+    }
 }
@@ -1,17 +1,30 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use alloy_primitives::{keccak256, Address, B256, U256};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use banderwagon::Element;
+use db::pruner::Pruner;
+use ssz::Decode;
 
 use crate::{
-    nodes::{CommitmentNode, Node},
-    storage::AccountStorageLayout,
+    diff::{diff, ValueDiff},
+    iterator::{StemIterator, TrieIterator},
+    nodes::{CommitmentNode, Node, NodeTrait},
+    proof::VerkleProof,
+    stem::Stem,
+    storage::{AccountStorageLayout, BasicData, HeaderLayout, TreeKeyHasher, DEFAULT_KEY_HASHER},
     utils::{b256_to_element, element_to_b256},
-    Db, TrieKey, TrieValue,
+    Db, TrieKey, TrieValue, WriteBatch,
 };
 
 pub struct Trie {
     root: Node,
     db: Box<Db>,
+    pruner: Option<Pruner<Element>>,
+    header_layout: HeaderLayout,
+    key_hasher: Arc<dyn TreeKeyHasher>,
+    /// Roots committed with [Trie::commit], by block number.
+    committed_roots: BTreeMap<u64, B256>,
 }
 
 impl Trie {
@@ -19,6 +32,10 @@ impl Trie {
         Self {
             root: Node::new(),
             db,
+            pruner: None,
+            header_layout: HeaderLayout::default(),
+            key_hasher: DEFAULT_KEY_HASHER.clone(),
+            committed_roots: BTreeMap::new(),
         }
     }
 
@@ -26,8 +43,62 @@ impl Trie {
         Self {
             root: Node::Commitment(CommitmentNode::new(b256_to_element(&root))),
             db,
+            pruner: None,
+            header_layout: HeaderLayout::default(),
+            key_hasher: DEFAULT_KEY_HASHER.clone(),
+            committed_roots: BTreeMap::new(),
         }
     }
+
+    /// Commits the trie as the state after the given block and returns its root.
+    ///
+    /// Committing a block that was already committed is handled as a reorg, so the roots of the
+    /// later blocks are dropped.
+    pub fn commit(&mut self, block_number: u64) -> Result<B256> {
+        let root = self.root()?;
+        self.committed_roots.split_off(&block_number);
+        self.committed_roots.insert(block_number, root);
+        Ok(root)
+    }
+
+    /// Returns the root committed for the given block.
+    pub fn root_at(&self, block_number: u64) -> Option<B256> {
+        self.committed_roots.get(&block_number).copied()
+    }
+
+    /// Re-opens the trie at the given root, which must be stored in the Db. Uncommitted changes
+    /// are discarded.
+    pub fn checkout(&mut self, root: B256) -> Result<()> {
+        let commitment = b256_to_element(&root);
+        if self.db.read(&commitment)?.is_none() {
+            bail!("Root missing from Db: {root:?}")
+        }
+        self.root = Node::Commitment(CommitmentNode::new(commitment));
+        Ok(())
+    }
+
+    /// Enables pruning of the nodes that are not reachable from the last `retained_roots` roots
+    /// returned by [Trie::root_commitment]. Pruned roots are also dropped from the committed roots.
+    ///
+    /// Pruning state is stored in the Db, so it should be enabled again when the Db is reopened.
+    /// Nodes that were stored before pruning was first enabled are only deleted if they are
+    /// written again, so pruning should be enabled before the trie is committed for the first
+    /// time.
+    pub fn enable_pruning(&mut self, retained_roots: usize) {
+        self.pruner = Some(Pruner::new(retained_roots));
+    }
+
+    /// Sets the layout of the account header leaves, used by the account methods. Defaults to
+    /// [HeaderLayout::Separate].
+    pub fn set_header_layout(&mut self, header_layout: HeaderLayout) {
+        self.header_layout = header_layout;
+    }
+
+    /// Sets the derivation of the account storage keys, used by the account methods. Defaults to
+    /// [DEFAULT_KEY_HASHER].
+    pub fn set_key_hasher(&mut self, key_hasher: impl TreeKeyHasher + 'static) {
+        self.key_hasher = Arc::new(key_hasher);
+    }
 }
 
 impl Trie {
@@ -39,24 +110,92 @@ impl Trie {
         self.root.insert(0, key, value, self.db.as_ref())
     }
 
+    /// Inserts all `(key, value)` pairs, updating the commitment of each touched node only once.
+    ///
+    /// Much faster than inserting keys one by one, when many keys are inserted at once.
+    pub fn insert_batch(
+        &mut self,
+        key_values: impl IntoIterator<Item = (TrieKey, TrieValue)>,
+    ) -> Result<()> {
+        let mut stems = BTreeMap::<Stem, BTreeMap<u8, TrieValue>>::new();
+        for (key, value) in key_values {
+            stems
+                .entry(key.stem())
+                .or_default()
+                .insert(key.last(), value);
+        }
+        self.root
+            .insert_stems(0, stems.into_iter().collect(), self.db.as_ref())
+    }
+
+    pub fn remove(&mut self, key: TrieKey) -> Result<()> {
+        self.root.remove(0, key, self.db.as_ref())
+    }
+
     pub fn root_commitment(&mut self) -> Result<Element> {
-        self.root.write_and_commit(self.db.as_mut())
+        let mut batch = WriteBatch::new();
+        #[cfg(feature = "rayon")]
+        let commitment = self.root.par_write_and_commit(&mut batch);
+        #[cfg(not(feature = "rayon"))]
+        let commitment = self.root.write_and_commit(&mut batch);
+
+        let pruned_roots = match &self.pruner {
+            Some(pruner) => pruner.commit(&mut batch, commitment, self.db.as_ref(), |bytes| {
+                Self::referenced_nodes(bytes)
+            })?,
+            None => vec![],
+        };
+        self.db.write_batch(batch)?;
+        // Nodes are kept in memory until the batch is written, so nothing is lost if it fails
+        self.root = Node::Commitment(CommitmentNode::new(commitment));
+        let pruned_roots = pruned_roots.iter().map(element_to_b256).collect::<Vec<_>>();
+        self.committed_roots
+            .retain(|_, root| !pruned_roots.contains(root));
+        Ok(commitment)
+    }
+
+    /// Returns commitments of the children of the encoded node.
+    fn referenced_nodes(bytes: &[u8]) -> Vec<Element> {
+        match Node::from_ssz_bytes(bytes) {
+            Ok(Node::Branch(branch_node)) => branch_node
+                .children()
+                .values()
+                .map(|node| node.commitment())
+                .collect(),
+            // Stored nodes are always valid, undecodable node just doesn't reference anything
+            _ => vec![],
+        }
     }
 
     pub fn root(&mut self) -> Result<B256> {
         Ok(element_to_b256(&self.root_commitment()?))
     }
 
+    /// Returns all changes between the tries with the given roots, which must both be stored in
+    /// the Db. See [diff].
+    pub fn diff(&self, old_root: B256, new_root: B256) -> Result<Vec<(TrieKey, ValueDiff)>> {
+        diff(old_root, new_root, self.db.as_ref())
+    }
+
+    /// Returns iterator over all `(key, value)` pairs, in the ascending order of keys.
+    pub fn iter(&self) -> TrieIterator<'_> {
+        TrieIterator::new(&self.root, self.db.as_ref())
+    }
+
+    /// Returns iterator over all stems and their values, in the ascending order of stems.
+    pub fn iter_stems(&self) -> StemIterator<'_> {
+        StemIterator::new(&self.root, self.db.as_ref())
+    }
+
+    /// Creates the proof of values (or their absence) for the given keys.
+    pub fn prove(&mut self, keys: &[TrieKey]) -> Result<VerkleProof> {
+        self.root_commitment()?;
+        VerkleProof::create(&mut self.root, keys, self.db.as_ref())
+    }
+
     pub fn create_eoa(&mut self, address: Address, balance: U256, nonce: u64) -> Result<()> {
-        let storage = AccountStorageLayout::new(address);
-        self.insert(storage.version_key(), TrieValue::ZERO)?;
-        self.insert(storage.balance_key(), balance)?;
-        self.insert(storage.nonce_key(), TrieValue::from(nonce))?;
-        self.insert(
-            storage.code_hash_key(),
-            TrieValue::from_le_bytes(keccak256([]).0),
-        )?;
-        Ok(())
+        let storage = self.account_storage(address);
+        self.insert_batch(storage.account_header(balance, nonce, None)?)
     }
 
     pub fn create_sc(
@@ -66,35 +205,204 @@ impl Trie {
         nonce: u64,
         code: Vec<u8>,
     ) -> Result<()> {
-        let storage = AccountStorageLayout::new(address);
-        self.insert(storage.version_key(), TrieValue::ZERO)?;
-        self.insert(storage.balance_key(), balance)?;
-        self.insert(storage.nonce_key(), TrieValue::from(nonce))?;
-        self.insert(
+        let storage = self.account_storage(address);
+        let mut key_values = storage.account_header(balance, nonce, Some(&code))?;
+        key_values.extend(storage.chunkify_code(&code));
+        self.insert_batch(key_values)
+    }
+
+    /// Returns the balance of the account, or None if account doesn't exist.
+    pub fn get_balance(&mut self, address: Address) -> Result<Option<U256>> {
+        let storage = self.account_storage(address);
+        match storage.header_layout() {
+            HeaderLayout::Separate => self.get(storage.balance_key()),
+            HeaderLayout::BasicData => Ok(self
+                .get_basic_data(&storage)?
+                .map(|basic_data| U256::from(basic_data.balance))),
+        }
+    }
+
+    /// Returns the nonce of the account, or None if account doesn't exist.
+    pub fn get_nonce(&mut self, address: Address) -> Result<Option<u64>> {
+        let storage = self.account_storage(address);
+        match storage.header_layout() {
+            HeaderLayout::Separate => {
+                let Some(nonce) = self.get(storage.nonce_key())? else {
+                    return Ok(None);
+                };
+                Ok(Some(u64::try_from(nonce)?))
+            }
+            HeaderLayout::BasicData => Ok(self
+                .get_basic_data(&storage)?
+                .map(|basic_data| basic_data.nonce)),
+        }
+    }
+
+    /// Returns the code hash of the account, or None if account doesn't exist.
+    pub fn get_code_hash(&mut self, address: Address) -> Result<Option<B256>> {
+        let code_hash = self.get(self.account_storage(address).code_hash_key())?;
+        Ok(code_hash.map(|code_hash| B256::from(code_hash.to_le_bytes::<32>())))
+    }
+
+    /// Returns the code size of the account, or None if account doesn't exist.
+    pub fn get_code_size(&mut self, address: Address) -> Result<Option<usize>> {
+        let storage = self.account_storage(address);
+        match storage.header_layout() {
+            HeaderLayout::Separate => {
+                if !self.account_exists(&storage)? {
+                    return Ok(None);
+                }
+                // Code size is not stored for EOAs
+                let code_size = self.get(storage.code_size_key())?.unwrap_or_default();
+                Ok(Some(usize::try_from(code_size)?))
+            }
+            HeaderLayout::BasicData => Ok(self
+                .get_basic_data(&storage)?
+                .map(|basic_data| basic_data.code_size as usize)),
+        }
+    }
+
+    /// Returns the value of the storage slot, or None if it isn't set.
+    pub fn get_storage(&mut self, address: Address, slot: U256) -> Result<Option<TrieValue>> {
+        self.get(self.account_storage(address).storage_slot_key(slot))
+    }
+
+    /// Returns the code of the account, or None if account doesn't exist.
+    ///
+    /// The code is reassembled from the chunks created by [AccountStorageLayout::chunkify_code].
+    pub fn get_code(&mut self, address: Address) -> Result<Option<Vec<u8>>> {
+        let Some(code_size) = self.get_code_size(address)? else {
+            return Ok(None);
+        };
+        let storage = self.account_storage(address);
+        let mut code = Vec::with_capacity(code_size.next_multiple_of(31));
+        for chunk_id in 0..code_size.div_ceil(31) {
+            let Some(chunk) = self.get(storage.code_key(chunk_id))? else {
+                bail!("Code chunk {chunk_id} missing for account {address}")
+            };
+            // The first byte is the number of leading push data bytes
+            code.extend_from_slice(&chunk.to_le_bytes::<32>()[1..]);
+        }
+        code.truncate(code_size);
+        Ok(Some(code))
+    }
+
+    pub fn set_balance(&mut self, address: Address, balance: U256) -> Result<()> {
+        let storage = self.existing_account(address)?;
+        match storage.header_layout() {
+            HeaderLayout::Separate => self.insert(storage.balance_key(), balance),
+            HeaderLayout::BasicData => self.update_basic_data(&storage, |basic_data| {
+                basic_data.balance = u128::try_from(balance)?;
+                Ok(())
+            }),
+        }
+    }
+
+    pub fn set_nonce(&mut self, address: Address, nonce: u64) -> Result<()> {
+        let storage = self.existing_account(address)?;
+        match storage.header_layout() {
+            HeaderLayout::Separate => self.insert(storage.nonce_key(), TrieValue::from(nonce)),
+            HeaderLayout::BasicData => self.update_basic_data(&storage, |basic_data| {
+                basic_data.nonce = nonce;
+                Ok(())
+            }),
+        }
+    }
+
+    /// Sets the value of the storage slot. Setting the zero value doesn't remove the slot.
+    pub fn set_storage(&mut self, address: Address, slot: U256, value: TrieValue) -> Result<()> {
+        let storage = self.existing_account(address)?;
+        self.insert(storage.storage_slot_key(slot), value)
+    }
+
+    /// Replaces the code of the account and updates its code hash and size.
+    pub fn set_code(&mut self, address: Address, code: Vec<u8>) -> Result<()> {
+        let storage = self.existing_account(address)?;
+        if storage.header_layout() == HeaderLayout::BasicData {
+            // Validated before the old chunks are removed, so the account is left unchanged
+            BasicData::check_code_size(code.len())?;
+        }
+        let old_code_size = self.get_code_size(address)?.unwrap_or_default();
+
+        let chunks = storage.chunkify_code(&code);
+        for chunk_id in chunks.len()..old_code_size.div_ceil(31) {
+            self.remove(storage.code_key(chunk_id))?;
+        }
+        match storage.header_layout() {
+            HeaderLayout::Separate => {
+                self.insert(storage.code_size_key(), TrieValue::from(code.len()))?
+            }
+            HeaderLayout::BasicData => self.update_basic_data(&storage, |basic_data| {
+                basic_data.code_size = code.len() as u32;
+                Ok(())
+            })?,
+        }
+        let mut key_values = vec![(
             storage.code_hash_key(),
             TrieValue::from_le_bytes(keccak256(&code).0),
-        )?;
-        self.insert(storage.code_size_key(), TrieValue::from(code.len()))?;
-        for (chunk_key, chunk_value) in storage.chunkify_code(&code) {
-            self.insert(chunk_key, chunk_value)?;
+        )];
+        key_values.extend(chunks);
+        self.insert_batch(key_values)
+    }
+
+    /// Returns the storage layout of the account, with the header layout and key hasher of the
+    /// trie.
+    pub fn account_storage(&self, address: Address) -> AccountStorageLayout {
+        AccountStorageLayout::new_with_key_hasher(
+            address,
+            self.header_layout,
+            self.key_hasher.clone(),
+        )
+    }
+
+    fn account_exists(&mut self, storage: &AccountStorageLayout) -> Result<bool> {
+        let key = match storage.header_layout() {
+            HeaderLayout::Separate => storage.version_key(),
+            HeaderLayout::BasicData => storage.basic_data_key(),
+        };
+        Ok(self.get(key)?.is_some())
+    }
+
+    fn existing_account(&mut self, address: Address) -> Result<AccountStorageLayout> {
+        let storage = self.account_storage(address);
+        if !self.account_exists(&storage)? {
+            bail!("Account doesn't exist: {address}")
         }
-        Ok(())
+        Ok(storage)
+    }
+
+    fn get_basic_data(&mut self, storage: &AccountStorageLayout) -> Result<Option<BasicData>> {
+        Ok(self
+            .get(storage.basic_data_key())?
+            .map(BasicData::from_value))
+    }
+
+    fn update_basic_data(
+        &mut self,
+        storage: &AccountStorageLayout,
+        update: impl FnOnce(&mut BasicData) -> Result<()>,
+    ) -> Result<()> {
+        let Some(mut basic_data) = self.get_basic_data(storage)? else {
+            bail!("Basic data missing from account header")
+        };
+        update(&mut basic_data)?;
+        self.insert(storage.basic_data_key(), basic_data.to_value())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{cell::Cell, rc::Rc, str::FromStr};
 
     use alloy_primitives::U256;
     use anyhow::Result;
     use ark_ff::UniformRand;
     use claims::{assert_none, assert_some_eq};
-    use db::memory_db::MemoryDb;
-    use rand::{rngs::StdRng, SeedableRng};
+    use db::{errors::DbError, memory_db::MemoryDb};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
     use rstest::rstest;
 
-    use crate::utils::fr_to_b256;
+    use crate::{crs::CRS, storage::Sha256KeyHasher, utils::fr_to_b256};
 
     use super::*;
 
@@ -191,6 +499,134 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn remove_same_stem() -> Result<()> {
+        let mut trie = init();
+
+        let key0 = TrieKey::new(B256::ZERO);
+        let value0 = TrieValue::ZERO;
+        let key1 = TrieKey::new(U256::from(1).into());
+        let value1 = TrieValue::from(1);
+
+        trie.insert(key0, value0)?;
+        trie.insert(key1, value1)?;
+        trie.root()?;
+
+        trie.remove(key1)?;
+        assert_none!(trie.get(key1)?);
+        assert_some_eq!(trie.get(key0)?, value0);
+
+        assert_eq!(
+            fr_to_b256(&trie.root_commitment()?.map_to_scalar_field()),
+            B256::from_str("0xff00a9f3f2d4f58fc23bceebf6b2310419ceac2c30445e2f374e571487715015")?,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_different_stems() -> Result<()> {
+        let mut trie = init();
+
+        let key0 = TrieKey::new(B256::ZERO);
+        let value0 = TrieValue::ZERO;
+        let key_max = TrieKey::new(U256::MAX.into());
+        let value_max = TrieValue::MAX;
+
+        trie.insert(key0, value0)?;
+        let expected_root = trie.root()?;
+
+        trie.insert(key_max, value_max)?;
+        trie.root()?;
+
+        trie.remove(key_max)?;
+        assert_none!(trie.get(key_max)?);
+        assert_some_eq!(trie.get(key0)?, value0);
+        assert_eq!(trie.root()?, expected_root);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_all() -> Result<()> {
+        let mut trie = init();
+
+        let key0 = TrieKey::new(B256::ZERO);
+        let key1 = TrieKey::new(U256::from(1).into());
+        let key_max = TrieKey::new(U256::MAX.into());
+
+        trie.insert(key0, TrieValue::ZERO)?;
+        trie.insert(key1, TrieValue::from(1))?;
+        trie.insert(key_max, TrieValue::MAX)?;
+        trie.root()?;
+
+        trie.remove(key0)?;
+        trie.remove(key1)?;
+        trie.remove(key_max)?;
+
+        assert_eq!(trie.root_commitment()?, Element::zero());
+        Ok(())
+    }
+
+    #[test]
+    fn remove_missing() -> Result<()> {
+        let mut trie = init();
+
+        let key0 = TrieKey::new(B256::ZERO);
+        trie.insert(key0, TrieValue::ZERO)?;
+        let root = trie.root()?;
+
+        trie.remove(TrieKey::new(U256::from(1).into()))?;
+        trie.remove(TrieKey::new(U256::MAX.into()))?;
+
+        assert_eq!(trie.root()?, root);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(12345, 10)]
+    #[case(12345, 100)]
+    fn remove_random(#[case] seed: u64, #[case] count: usize) -> Result<()> {
+        let mut trie = init();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // Removed keys share the first byte that isn't used by kept keys, so removing them
+        // removes the whole subtree (branches with a single leaf are not collapsed)
+        let kept_keys = (0..count)
+            .map(|i| {
+                let mut key = B256::random_with(&mut rng);
+                key[0] = i as u8;
+                TrieKey::new(key)
+            })
+            .collect::<Vec<_>>();
+        for key in &kept_keys {
+            trie.insert(*key, U256::rand(&mut rng))?;
+        }
+        let expected_root = trie.root()?;
+
+        let removed_keys = (0..count)
+            .map(|_| {
+                let mut key = B256::random_with(&mut rng);
+                key[0] = u8::MAX - (count as u8);
+                TrieKey::new(key)
+            })
+            .collect::<Vec<_>>();
+        for key in &removed_keys {
+            trie.insert(*key, U256::rand(&mut rng))?;
+        }
+        trie.root()?;
+
+        for key in &removed_keys {
+            trie.remove(*key)?;
+        }
+        for key in &removed_keys {
+            assert_none!(trie.get(*key)?);
+        }
+        assert_eq!(trie.root()?, expected_root);
+
+        Ok(())
+    }
+
     #[rstest]
     #[case(12345, 10)]
     #[case(12345, 100)]
@@ -220,4 +656,409 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    #[case(12345, 10)]
+    #[case(12345, 1000)]
+    fn insert_batch(#[case] seed: u64, #[case] count: usize) -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut key_values = vec![];
+        while key_values.len() < count {
+            let key = TrieKey::new(B256::random_with(&mut rng));
+            // Also add key with the same stem, and overwrite the first value
+            let same_stem_key = TrieKey::from_stem_and_last_byte(&key.stem(), key.last() / 2);
+            key_values.push((key, U256::rand(&mut rng)));
+            key_values.push((same_stem_key, U256::rand(&mut rng)));
+            key_values.push((key, U256::rand(&mut rng)));
+        }
+        let (first, second) = key_values.split_at(count / 2);
+
+        let mut expected_trie = init();
+        for (key, value) in &key_values {
+            expected_trie.insert(*key, *value)?;
+        }
+
+        let mut trie = init();
+        trie.insert_batch(first.iter().copied())?;
+        // Second batch is inserted on top of the committed trie
+        trie.root()?;
+        trie.insert_batch(second.iter().copied())?;
+        assert_eq!(trie.root()?, expected_trie.root()?);
+
+        for (key, _) in key_values {
+            assert_eq!(trie.get(key)?, expected_trie.get(key)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn commit_and_checkout() -> Result<()> {
+        let mut trie = init();
+        let key = TrieKey::new(B256::repeat_byte(1));
+        let other_key = TrieKey::new(B256::repeat_byte(2));
+        let empty_root = trie.commit(0)?;
+        for block in 1..=5 {
+            trie.insert(key, U256::from(block))?;
+            trie.commit(block)?;
+        }
+        let root_3 = trie.root_at(3).unwrap();
+        let root_5 = trie.root_at(5).unwrap();
+
+        // Revert to block 2 and replay different block 3
+        trie.insert(other_key, U256::from(10))?;
+        trie.checkout(trie.root_at(2).unwrap())?;
+        assert_some_eq!(trie.get(key)?, U256::from(2));
+        // Uncommitted changes were discarded
+        assert_none!(trie.get(other_key)?);
+
+        trie.insert(other_key, U256::from(3))?;
+        let forked_root_3 = trie.commit(3)?;
+        assert_ne!(forked_root_3, root_3);
+        assert_eq!(trie.root_at(3), Some(forked_root_3));
+        // Later blocks of the original chain were dropped
+        assert_none!(trie.root_at(4));
+        assert_none!(trie.root_at(5));
+
+        // Original chain is still stored
+        trie.checkout(root_3)?;
+        assert_some_eq!(trie.get(key)?, U256::from(3));
+        assert_none!(trie.get(other_key)?);
+        trie.checkout(root_5)?;
+        assert_some_eq!(trie.get(key)?, U256::from(5));
+        trie.checkout(empty_root)?;
+        assert_none!(trie.get(key)?);
+
+        // Valid commitment that is not in the Db
+        assert!(trie.checkout(element_to_b256(&CRS[0])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pruned_history() -> Result<()> {
+        let mut trie = init();
+        trie.enable_pruning(2);
+        let key = TrieKey::new(B256::repeat_byte(1));
+        for block in 1..=4 {
+            trie.insert(key, U256::from(block))?;
+            trie.commit(block)?;
+        }
+
+        assert_none!(trie.root_at(1));
+        assert_none!(trie.root_at(2));
+        trie.checkout(trie.root_at(3).unwrap())?;
+        assert_some_eq!(trie.get(key)?, U256::from(3));
+        trie.checkout(trie.root_at(4).unwrap())?;
+        assert_some_eq!(trie.get(key)?, U256::from(4));
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write() -> Result<()> {
+        // Db that fails to write batches while `fail` is set
+        struct FailingDb {
+            db: MemoryDb<Element, Vec<u8>>,
+            fail: Rc<Cell<bool>>,
+        }
+
+        impl db::Db<Element, Vec<u8>> for FailingDb {
+            fn write(&mut self, key: Element, value: Vec<u8>) -> Result<(), DbError> {
+                self.db.write(key, value)
+            }
+
+            fn read(&self, key: &Element) -> Result<Option<Vec<u8>>, DbError> {
+                self.db.read(key)
+            }
+
+            fn write_batch(&mut self, batch: WriteBatch) -> Result<(), DbError> {
+                if self.fail.get() {
+                    return Err(DbError::Error);
+                }
+                self.db.write_batch(batch)
+            }
+        }
+
+        let fail = Rc::new(Cell::new(false));
+        let mut trie = Trie::new(Box::new(FailingDb {
+            db: MemoryDb::new(),
+            fail: fail.clone(),
+        }));
+        let mut expected_trie = init();
+        let mut rng = StdRng::seed_from_u64(12345);
+        let key_values = (0..200)
+            .map(|_| {
+                (
+                    TrieKey::new(B256::random_with(&mut rng)),
+                    U256::rand(&mut rng),
+                )
+            })
+            .collect::<Vec<_>>();
+        for trie in [&mut trie, &mut expected_trie] {
+            trie.insert_batch(key_values[..100].iter().copied())?;
+            trie.root()?;
+            trie.insert_batch(key_values[100..].iter().copied())?;
+        }
+
+        fail.set(true);
+        assert!(trie.root().is_err());
+        // Modified nodes were kept, so nothing is lost
+        fail.set(false);
+        assert_eq!(trie.root()?, expected_trie.root()?);
+        for (key, value) in key_values {
+            assert_some_eq!(trie.get(key)?, value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn basic_data_layout() -> Result<()> {
+        let address = Address::repeat_byte(1);
+        let code = vec![0x5b; 100];
+        let mut trie = init();
+        trie.set_header_layout(HeaderLayout::BasicData);
+        trie.create_sc(address, U256::from(100), 1, code.clone())?;
+
+        let storage = AccountStorageLayout::new_with_layout(address, HeaderLayout::BasicData);
+        assert_some_eq!(
+            trie.get(storage.basic_data_key())?,
+            BasicData::new(U256::from(100), 1, code.len())?.to_value()
+        );
+        assert_some_eq!(
+            trie.get(storage.code_hash_key())?,
+            TrieValue::from_le_bytes(keccak256(&code).0)
+        );
+        // Other header leaves of the separate layout are not used
+        let separate_storage = AccountStorageLayout::new(address);
+        assert_none!(trie.get(separate_storage.nonce_key())?);
+        assert_none!(trie.get(separate_storage.code_hash_key())?);
+        assert_none!(trie.get(separate_storage.code_size_key())?);
+
+        let mut separate_trie = init();
+        separate_trie.create_sc(address, U256::from(100), 1, code)?;
+        assert_ne!(trie.root()?, separate_trie.root()?);
+        Ok(())
+    }
+
+    #[test]
+    fn sha256_key_hasher() -> Result<()> {
+        let address = Address::repeat_byte(1);
+        let code = vec![0x5b; 100];
+        let mut trie = init();
+        trie.set_key_hasher(Sha256KeyHasher);
+        trie.create_sc(address, U256::from(100), 1, code.clone())?;
+        trie.set_storage(address, U256::from(1000), U256::from(10))?;
+
+        assert_some_eq!(trie.get_balance(address)?, U256::from(100));
+        assert_some_eq!(trie.get_code(address)?, code.clone());
+        assert_some_eq!(trie.get_storage(address, U256::from(1000))?, U256::from(10));
+        let storage = AccountStorageLayout::new_with_key_hasher(
+            address,
+            HeaderLayout::Separate,
+            Arc::new(Sha256KeyHasher),
+        );
+        assert_some_eq!(trie.get(storage.balance_key())?, U256::from(100));
+
+        // Accounts created with the default hasher are stored under different keys
+        assert_none!(trie.get(AccountStorageLayout::new(address).balance_key())?);
+        let mut pedersen_trie = init();
+        pedersen_trie.create_sc(address, U256::from(100), 1, code)?;
+        assert_ne!(trie.root()?, pedersen_trie.root()?);
+        Ok(())
+    }
+
+    #[test]
+    fn pruning() -> Result<()> {
+        let mut trie = init();
+        trie.enable_pruning(2);
+        let mut rng = StdRng::seed_from_u64(12345);
+
+        let mut keys = vec![];
+        let mut entries = BTreeMap::new();
+        let mut roots = vec![];
+        for round in 0..10 {
+            for _ in 0..100 {
+                // Update existing keys after the first round
+                let key = if round > 0 && rng.gen_bool(0.5) {
+                    keys[rng.gen_range(0..keys.len())]
+                } else {
+                    TrieKey::new(B256::random_with(&mut rng))
+                };
+                keys.push(key);
+                let value = U256::rand(&mut rng);
+                trie.insert(key, value)?;
+                entries.insert(key, value);
+            }
+            roots.push((trie.root()?, entries.clone()));
+
+            // All nodes of the retained roots are still stored
+            for (root, entries) in roots.iter().rev().take(2) {
+                let root_node = Node::Commitment(CommitmentNode::new(b256_to_element(root)));
+                assert_eq!(
+                    TrieIterator::new(&root_node, trie.db.as_ref()).collect::<Result<Vec<_>>>()?,
+                    entries.clone().into_iter().collect::<Vec<_>>()
+                );
+            }
+        }
+
+        for (root, _) in &roots[..roots.len() - 2] {
+            assert_none!(trie.db.read(&b256_to_element(root))?);
+        }
+        Ok(())
+    }
+
+    #[rstest]
+    fn account_getters(
+        #[values(HeaderLayout::Separate, HeaderLayout::BasicData)] layout: HeaderLayout,
+    ) -> Result<()> {
+        let mut trie = init();
+        trie.set_header_layout(layout);
+        let eoa = Address::repeat_byte(1);
+        let sc = Address::repeat_byte(2);
+        // PUSH32 at the end of the first chunk, so the second chunk starts with push data
+        let mut code = vec![0x5b; 30];
+        code.push(0x7f);
+        code.extend([0xff; 32]);
+        code.push(0x00);
+
+        trie.create_eoa(eoa, U256::from(100), 1)?;
+        trie.create_sc(sc, U256::from(200), 2, code.clone())?;
+        trie.root()?;
+
+        assert_some_eq!(trie.get_balance(eoa)?, U256::from(100));
+        assert_some_eq!(trie.get_nonce(eoa)?, 1);
+        assert_some_eq!(trie.get_code_hash(eoa)?, keccak256([]));
+        assert_some_eq!(trie.get_code_size(eoa)?, 0);
+        assert_some_eq!(trie.get_code(eoa)?, vec![]);
+
+        assert_some_eq!(trie.get_balance(sc)?, U256::from(200));
+        assert_some_eq!(trie.get_nonce(sc)?, 2);
+        assert_some_eq!(trie.get_code_hash(sc)?, keccak256(&code));
+        assert_some_eq!(trie.get_code_size(sc)?, code.len());
+        assert_some_eq!(trie.get_code(sc)?, code);
+        assert_none!(trie.get_storage(sc, U256::ZERO)?);
+
+        let missing = Address::repeat_byte(3);
+        assert_none!(trie.get_balance(missing)?);
+        assert_none!(trie.get_nonce(missing)?);
+        assert_none!(trie.get_code_hash(missing)?);
+        assert_none!(trie.get_code_size(missing)?);
+        assert_none!(trie.get_code(missing)?);
+        Ok(())
+    }
+
+    #[rstest]
+    fn account_setters(
+        #[values(HeaderLayout::Separate, HeaderLayout::BasicData)] layout: HeaderLayout,
+    ) -> Result<()> {
+        let mut trie = init();
+        trie.set_header_layout(layout);
+        let address = Address::repeat_byte(1);
+        trie.create_sc(address, U256::from(100), 1, vec![0x5b; 100])?;
+        trie.root()?;
+
+        trie.set_balance(address, U256::from(200))?;
+        trie.set_nonce(address, 2)?;
+        trie.set_storage(address, U256::from(1), U256::from(10))?;
+        trie.set_storage(address, U256::from(1000), U256::from(20))?;
+        // Shorter code, so the last chunks are removed
+        trie.set_code(address, vec![0x00; 40])?;
+
+        assert_some_eq!(trie.get_balance(address)?, U256::from(200));
+        assert_some_eq!(trie.get_nonce(address)?, 2);
+        assert_some_eq!(trie.get_storage(address, U256::from(1))?, U256::from(10));
+        assert_some_eq!(trie.get_storage(address, U256::from(1000))?, U256::from(20));
+        assert_some_eq!(trie.get_code(address)?, vec![0x00; 40]);
+
+        let mut expected_trie = init();
+        expected_trie.set_header_layout(layout);
+        expected_trie.create_sc(address, U256::from(200), 2, vec![0x00; 40])?;
+        expected_trie.insert(
+            AccountStorageLayout::new(address).storage_slot_key(U256::from(1)),
+            U256::from(10),
+        )?;
+        expected_trie.insert(
+            AccountStorageLayout::new(address).storage_slot_key(U256::from(1000)),
+            U256::from(20),
+        )?;
+        assert_eq!(trie.root()?, expected_trie.root()?);
+
+        let missing = Address::repeat_byte(2);
+        assert!(trie.set_balance(missing, U256::from(1)).is_err());
+        assert!(trie.set_nonce(missing, 1).is_err());
+        assert!(trie
+            .set_storage(missing, U256::ZERO, U256::from(1))
+            .is_err());
+        assert!(trie.set_code(missing, vec![0x00]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn set_code_basic_data() -> Result<()> {
+        let mut trie = init();
+        trie.set_header_layout(HeaderLayout::BasicData);
+        let address = Address::repeat_byte(1);
+        trie.create_sc(address, U256::from(100), 1, vec![0x5b; 100])?;
+        let storage = trie.account_storage(address);
+        let basic_data = BasicData {
+            version: 1,
+            ..BasicData::new(U256::from(100), 1, 100)?
+        };
+        trie.insert(storage.basic_data_key(), basic_data.to_value())?;
+
+        // Code size doesn't fit the basic data leaf
+        assert!(trie
+            .set_code(address, vec![0x00; BasicData::MAX_CODE_SIZE + 1])
+            .is_err());
+        assert_some_eq!(trie.get_code(address)?, vec![0x5b; 100]);
+
+        // Only the code size is updated
+        trie.set_code(address, vec![0x00; 40])?;
+        assert_some_eq!(
+            trie.get(storage.basic_data_key())?,
+            BasicData {
+                code_size: 40,
+                ..basic_data
+            }
+            .to_value()
+        );
+        assert_some_eq!(trie.get_code(address)?, vec![0x00; 40]);
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[rstest]
+    #[case(12345, 10)]
+    #[case(12345, 1000)]
+    #[case(12345, 10000)]
+    fn parallel_commit(#[case] seed: u64, #[case] count: usize) -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let key_values = (0..count)
+            .map(|_| {
+                (
+                    TrieKey::new(B256::random_with(&mut rng)),
+                    U256::rand(&mut rng),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut sequential = init();
+        let mut parallel = init();
+        // Second half is committed on top of the already committed first half
+        for key_values in key_values.chunks(count / 2) {
+            sequential.insert_batch(key_values.iter().copied())?;
+            parallel.insert_batch(key_values.iter().copied())?;
+
+            let mut batch = WriteBatch::new();
+            let sequential_root = sequential.root.write_and_commit(&mut batch);
+            sequential.db.write_batch(batch)?;
+            sequential.root = Node::Commitment(CommitmentNode::new(sequential_root));
+            assert_eq!(parallel.root_commitment()?, sequential_root);
+        }
+
+        let sequential_root = sequential.root()?;
+        let mut parallel = Trie::new_with_root(sequential_root, parallel.db);
+        for (key, value) in key_values {
+            assert_some_eq!(parallel.get(key)?, value);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,42 @@
+#[cfg(all(test, feature = "rocksdb"))]
+mod rocks_db {
+    use alloy_primitives::{B256, U256};
+    use anyhow::Result;
+    use claims::{assert_none, assert_some_eq};
+    use db::rocks_db::RocksDb;
+    use rand::{rngs::StdRng, SeedableRng};
+    use tempfile::TempDir;
+    use verkle::{Trie, TrieKey, TrieValue};
+
+    #[test]
+    fn reopen() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut rng = StdRng::seed_from_u64(12345);
+
+        let key_values = (0..100)
+            .map(|_| {
+                (
+                    TrieKey::new(B256::random_with(&mut rng)),
+                    TrieValue::from_le_bytes(B256::random_with(&mut rng).0),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let root = {
+            let mut trie = Trie::new(Box::new(RocksDb::open(dir.path())?));
+            for (key, value) in &key_values {
+                trie.insert(*key, *value)?;
+            }
+            trie.root()?
+        };
+
+        let mut trie = Trie::new_with_root(root, Box::new(RocksDb::open(dir.path())?));
+        for (key, value) in key_values {
+            assert_some_eq!(trie.get(key)?, value);
+        }
+        assert_none!(trie.get(TrieKey::new(U256::MAX.into()))?);
+        assert_eq!(trie.root()?, root);
+
+        Ok(())
+    }
+}